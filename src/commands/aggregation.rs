@@ -0,0 +1,245 @@
+use serde_json::{Map, Value};
+use std::io;
+use std::io::Write;
+use tantivy::aggregation::agg_req::{Aggregation, Aggregations};
+use tantivy::aggregation::agg_result::AggregationResults;
+use tantivy::aggregation::intermediate_agg_result::IntermediateAggregationResults;
+use tantivy::aggregation::{AggContextParams, DistributedAggregationCollector};
+use tantivy::query::Query;
+use tantivy::{Searcher, TantivyError};
+
+use crate::commands::output::{csv_cell, write_table};
+
+/// Width of the bar of the bucket with the most documents.
+const MAX_BAR_WIDTH: usize = 40;
+
+/// Parses an aggregation request, naming the aggregation at fault if it is
+/// invalid.
+pub fn parse_aggregations(json: &str) -> tantivy::Result<Aggregations> {
+    let request: Value = serde_json::from_str(json).map_err(|e| {
+        TantivyError::InvalidArgument(format!("Invalid aggregation request: {}", e))
+    })?;
+    aggregations_from_value(request)
+}
+
+/// Same as `parse_aggregations`, for a request already parsed as JSON.
+pub fn aggregations_from_value(request: Value) -> tantivy::Result<Aggregations> {
+    let Value::Object(aggregations) = &request else {
+        return Err(TantivyError::InvalidArgument(
+            "Invalid aggregation request: expected an object of named aggregations".to_string(),
+        ));
+    };
+    check_aggregations("", aggregations)?;
+    serde_json::from_value(request)
+        .map_err(|e| TantivyError::InvalidArgument(format!("Invalid aggregation request: {}", e)))
+}
+
+/// Checks each aggregation, and its sub-aggregations before it, so that the
+/// error points at the innermost invalid one.
+fn check_aggregations(parent_path: &str, aggregations: &Map<String, Value>) -> tantivy::Result<()> {
+    for (name, aggregation) in aggregations {
+        let path = if parent_path.is_empty() {
+            name.clone()
+        } else {
+            format!("{}.{}", parent_path, name)
+        };
+        if let Some(Value::Object(sub_aggregations)) = aggregation.get("aggs") {
+            check_aggregations(&path, sub_aggregations)?;
+        }
+        serde_json::from_value::<Aggregation>(aggregation.clone()).map_err(|e| {
+            TantivyError::InvalidArgument(format!("Invalid aggregation `{}`: {}", path, e))
+        })?;
+    }
+    Ok(())
+}
+
+/// Runs the aggregations on the documents matching each query, with its
+/// searcher, and merges their results.
+pub fn aggregate_indexes(
+    searches: &[(&Searcher, &dyn Query)],
+    aggregations: Aggregations,
+    context: AggContextParams,
+) -> tantivy::Result<AggregationResults> {
+    let collector =
+        DistributedAggregationCollector::from_aggs(aggregations.clone(), context.clone());
+    let mut merged_results: Option<IntermediateAggregationResults> = None;
+    for (searcher, query) in searches {
+        let results = searcher.search(*query, &collector)?;
+        match &mut merged_results {
+            Some(merged_results) => merged_results.merge_fruits(results)?,
+            None => merged_results = Some(results),
+        }
+    }
+    merged_results
+        .unwrap_or_default()
+        .into_final_result(aggregations, context.limits)
+}
+
+/// Writes aggregation results as text tables: one row per bucket, with a bar
+/// proportional to its document count, or one row per statistic.
+pub fn write_aggregation_tables<W: Write>(
+    wrt: &mut W,
+    results: &AggregationResults,
+) -> io::Result<()> {
+    let results = serde_json::to_value(results)?;
+    if let Value::Object(results) = results {
+        write_results(wrt, "", &results)?;
+    }
+    Ok(())
+}
+
+fn write_results<W: Write>(
+    wrt: &mut W,
+    parent_path: &str,
+    results: &Map<String, Value>,
+) -> io::Result<()> {
+    for (name, result) in results {
+        let path = if parent_path.is_empty() {
+            name.clone()
+        } else {
+            format!("{} > {}", parent_path, name)
+        };
+        let Value::Object(result) = result else {
+            continue;
+        };
+        match result.get("buckets") {
+            Some(Value::Array(buckets)) => {
+                let buckets: Vec<(Option<&String>, &Value)> =
+                    buckets.iter().map(|bucket| (None, bucket)).collect();
+                write_buckets(wrt, &path, &buckets)?;
+            }
+            // Keyed range and histogram buckets.
+            Some(Value::Object(buckets)) => {
+                let buckets: Vec<(Option<&String>, &Value)> = buckets
+                    .iter()
+                    .map(|(key, bucket)| (Some(key), bucket))
+                    .collect();
+                write_buckets(wrt, &path, &buckets)?;
+            }
+            _ => write_metric(wrt, &path, result)?,
+        }
+    }
+    Ok(())
+}
+
+fn write_buckets<W: Write>(
+    wrt: &mut W,
+    path: &str,
+    buckets: &[(Option<&String>, &Value)],
+) -> io::Result<()> {
+    let doc_counts: Vec<u64> = buckets
+        .iter()
+        .map(|(_, bucket)| bucket["doc_count"].as_u64().unwrap_or(0))
+        .collect();
+    let max_doc_count = doc_counts.iter().copied().max().unwrap_or(0).max(1);
+    let header = vec!["key".to_string(), "doc_count".to_string(), String::new()];
+    let rows: Vec<Vec<String>> = buckets
+        .iter()
+        .zip(&doc_counts)
+        .map(|((key, bucket), doc_count)| {
+            let bar_width = (doc_count * MAX_BAR_WIDTH as u64).div_ceil(max_doc_count) as usize;
+            vec![
+                bucket_key(*key, bucket),
+                doc_count.to_string(),
+                "█".repeat(bar_width),
+            ]
+        })
+        .collect();
+    writeln!(wrt, "{}", path)?;
+    write_table(wrt, &header, &rows)?;
+    writeln!(wrt)?;
+    // Sub-aggregations are the objects of a bucket.
+    for (key, bucket) in buckets {
+        let sub_results: Map<String, Value> = bucket
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter(|(_, value)| value.is_object())
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        let bucket_path = format!("{} [{}]", path, bucket_key(*key, bucket));
+        write_results(wrt, &bucket_path, &sub_results)?;
+    }
+    Ok(())
+}
+
+/// Key of a bucket: its key in keyed results, else its formatted key.
+fn bucket_key(key: Option<&String>, bucket: &Value) -> String {
+    if let Some(key) = key {
+        return key.clone();
+    }
+    csv_cell(bucket.get("key_as_string").or_else(|| bucket.get("key")))
+}
+
+/// Writes a single value metric on one line, and the other metrics, like
+/// `stats`, as a table of their values.
+fn write_metric<W: Write>(wrt: &mut W, path: &str, result: &Map<String, Value>) -> io::Result<()> {
+    if let (1, Some(value)) = (result.len(), result.get("value")) {
+        let value = match value {
+            Value::Null => "-".to_string(),
+            value => csv_cell(Some(value)),
+        };
+        writeln!(wrt, "{}: {}", path, value)?;
+        return writeln!(wrt);
+    }
+    let header = vec!["stat".to_string(), "value".to_string()];
+    let rows: Vec<Vec<String>> = result
+        .iter()
+        .map(|(stat, value)| vec![stat.clone(), csv_cell(Some(value))])
+        .collect();
+    writeln!(wrt, "{}", path)?;
+    write_table(wrt, &header, &rows)?;
+    writeln!(wrt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_index::create_index;
+    use tantivy::aggregation::{AggContextParams, AggregationCollector};
+    use tantivy::doc;
+    use tantivy::query::AllQuery;
+    use tantivy::schema::{Schema, FAST, STRING};
+
+    #[test]
+    fn invalid_aggregations_are_named() {
+        let error = parse_aggregations(
+            r#"{"by_severity": {"terms": {"field": "severity"},
+                "aggs": {"latency": {"average": {"field": "latency"}}}}}"#,
+        )
+        .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("Invalid aggregation `by_severity.latency`"));
+        assert!(parse_aggregations(r#"{"by_severity": {"terms": {"field": "severity"}}}"#).is_ok());
+    }
+
+    #[test]
+    fn aggregation_tables() {
+        let mut schema_builder = Schema::builder();
+        let severity = schema_builder.add_text_field("severity", STRING | FAST);
+        let latency = schema_builder.add_u64_field("latency", FAST);
+        let docs = [("error", 10u64), ("info", 20), ("info", 30)].map(
+            |(severity_text, latency_ms)| doc!(severity => severity_text, latency => latency_ms),
+        );
+        let searcher = create_index(schema_builder.build(), docs)
+            .reader()
+            .unwrap()
+            .searcher();
+        let aggregations = parse_aggregations(
+            r#"{"severities": {"terms": {"field": "severity"}},
+                "latency": {"stats": {"field": "latency"}}}"#,
+        )
+        .unwrap();
+        let collector = AggregationCollector::from_aggs(aggregations, AggContextParams::default());
+        let results = searcher.search(&AllQuery, &collector).unwrap();
+        let mut buffer = Vec::new();
+        write_aggregation_tables(&mut buffer, &results).unwrap();
+        let output = String::from_utf8(buffer).unwrap();
+        assert!(output.starts_with(
+            "latency\nstat   value\n-----  -----\navg    20.0\ncount  3\nmax    30.0\n"
+        ));
+        assert!(output.contains(&format!("info   2          {}\n", "█".repeat(40))));
+        assert!(output.contains(&format!("error  1          {}\n", "█".repeat(20))));
+    }
+}
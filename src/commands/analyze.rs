@@ -0,0 +1,109 @@
+use clap::ArgMatches;
+use std::path::Path;
+use std::path::PathBuf;
+use tantivy::query::QueryParser;
+use tantivy::schema::{Field, Term};
+use tantivy::tokenizer::TokenStream;
+use tantivy::{Index, TantivyError};
+
+use crate::commands::tokenizers::open_index;
+
+pub fn run_analyze_cli(matches: &ArgMatches) -> Result<(), String> {
+    let index_directory = PathBuf::from(matches.get_one::<String>("index").unwrap());
+    let field_name = matches.get_one::<String>("field").unwrap();
+    let text = matches.get_one::<String>("text").unwrap();
+    run_analyze(&index_directory, field_name, text).map_err(|e| format!("{:?}", e))
+}
+
+fn run_analyze(directory: &Path, field_name: &str, text: &str) -> tantivy::Result<()> {
+    let index = open_index(directory)?;
+    let schema = index.schema();
+    let field = schema
+        .get_field(field_name)
+        .map_err(|_| TantivyError::FieldNotFound(field_name.to_string()))?;
+
+    let mut text_analyzer = index.tokenizer_for_field(field)?;
+    println!("Tokens");
+    println!("position\toffsets\ttoken");
+    let mut token_stream = text_analyzer.token_stream(text);
+    while token_stream.advance() {
+        let token = token_stream.token();
+        println!(
+            "{}\t{}..{}\t{}",
+            token.position, token.offset_from, token.offset_to, token.text
+        );
+    }
+
+    println!();
+    println!("Query terms");
+    for term in query_terms(&index, field, text) {
+        println!("{}", term);
+    }
+    Ok(())
+}
+
+/// Characters with a meaning in the query syntax, which are escaped so that
+/// the text is parsed as plain words.
+const QUERY_SYNTAX_CHARS: &[char] = &[
+    '^', '`', ':', '{', '}', '"', '\'', '[', ']', '(', ')', '\\', '-',
+];
+
+/// Returns the terms the query parser generates for `text` in `field`.
+///
+/// The query syntax of the text is escaped, so that `other_field:value` or an
+/// unbalanced quote are searched as words of `field`. Whatever remains
+/// invalid, such as a dangling `AND`, is skipped.
+fn query_terms(index: &Index, field: Field, text: &str) -> Vec<String> {
+    let mut escaped_text = String::with_capacity(text.len());
+    for c in text.chars() {
+        if QUERY_SYNTAX_CHARS.contains(&c) {
+            escaped_text.push('\\');
+        }
+        escaped_text.push(c);
+    }
+    let query_parser = QueryParser::new(index.schema(), vec![field], index.tokenizers().clone());
+    let (query, _errors) = query_parser.parse_query_lenient(&escaped_text);
+    let mut terms = Vec::new();
+    query.query_terms(&mut |term, _| terms.push(term_text(term)));
+    terms
+}
+
+fn term_text(term: &Term) -> String {
+    match term.value().as_str() {
+        Some(text) => text.to_string(),
+        None => format!("{:?}", term),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_index::create_index;
+    use tantivy::schema::{Schema, TEXT};
+
+    fn test_index() -> (Index, Field) {
+        let mut schema_builder = Schema::builder();
+        let body = schema_builder.add_text_field("body", TEXT);
+        schema_builder.add_text_field("title", TEXT);
+        (create_index(schema_builder.build(), []), body)
+    }
+
+    #[test]
+    fn query_terms_of_plain_text() {
+        let (index, body) = test_index();
+        assert_eq!(
+            query_terms(&index, body, "The Running Foxes"),
+            vec!["the", "running", "foxes"]
+        );
+    }
+
+    #[test]
+    fn query_syntax_is_analyzed_as_text_of_the_field() {
+        let (index, body) = test_index();
+        assert_eq!(query_terms(&index, body, r#"foo "bar"#), vec!["foo", "bar"]);
+        assert_eq!(
+            query_terms(&index, body, "title:paris (france"),
+            vec!["title", "paris", "france"]
+        );
+    }
+}
@@ -0,0 +1,298 @@
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::thread;
+use tantivy::collector::Count;
+use tantivy::query::QueryParser;
+use tantivy::{Searcher, TantivyDocument, TantivyError};
+
+use crate::commands::projection::FieldProjection;
+use crate::commands::query_settings::{index_query_parser, QuerySettings};
+use crate::commands::search::{check_top_docs_window, search_top_docs, Hit, SortBy};
+use crate::commands::tokenizers::open_index;
+
+/// Options of the queries of a batch, which a query of the queries file can
+/// override.
+pub struct BatchOptions {
+    pub limit: usize,
+    pub offset: usize,
+    pub sort_by: Option<SortBy>,
+    pub lenient: bool,
+    pub num_threads: usize,
+}
+
+/// A query of the queries file, given as a JSON object.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct BatchQuery {
+    query: String,
+    limit: Option<usize>,
+    offset: Option<usize>,
+    sort_by: Option<String>,
+    lenient: Option<bool>,
+}
+
+/// The results of a query of the batch, or the reason it failed.
+#[derive(Serialize)]
+struct BatchResult {
+    query: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    num_hits: Option<usize>,
+    /// `None` only if the query failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hits: Option<Vec<Hit>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    query_errors: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl BatchResult {
+    fn failed(query: String, error: String) -> BatchResult {
+        BatchResult {
+            query,
+            num_hits: None,
+            hits: None,
+            query_errors: Vec::new(),
+            error: Some(error),
+        }
+    }
+}
+
+/// Parses a line of the queries file: a JSON object if it starts with `{`,
+/// the query itself otherwise. Returns `None` for blank lines.
+fn parse_query_line(line: &str) -> Option<Result<BatchQuery, String>> {
+    let line = line.trim();
+    if line.is_empty() {
+        return None;
+    }
+    if !line.starts_with('{') {
+        return Some(Ok(BatchQuery {
+            query: line.to_string(),
+            limit: None,
+            offset: None,
+            sort_by: None,
+            lenient: None,
+        }));
+    }
+    Some(serde_json::from_str(line).map_err(|e| format!("Invalid query line: {}", e)))
+}
+
+/// Runs every query of `queries_file` and writes the results of each query as
+/// a JSON line, in the order of the file.
+///
+/// The queries are spread over `num_threads` threads, sharing one searcher
+/// and one query parser. A failing query does not stop the batch: its error
+/// is written in place of its hits.
+pub fn run_batch_search(
+    directory: &Path,
+    queries_file: &Path,
+    options: BatchOptions,
+    query_settings: QuerySettings,
+    fields: Option<Vec<String>>,
+) -> tantivy::Result<()> {
+    let index = open_index(directory)?;
+    let projection = match fields {
+        Some(fields) => FieldProjection::new(&index.schema(), &fields)?,
+        None => FieldProjection::all_stored(),
+    };
+    let query_parser = index_query_parser(&index, directory, query_settings)?;
+    let searcher = index.reader()?.searcher();
+    let queries: Vec<(String, Result<BatchQuery, String>)> = fs::read_to_string(queries_file)?
+        .lines()
+        .filter_map(|line| parse_query_line(line).map(|query| (line.trim().to_string(), query)))
+        .collect();
+
+    let (query_sender, query_receiver) = crossbeam_channel::unbounded();
+    for (query_ord, query) in queries.into_iter().enumerate() {
+        query_sender.send((query_ord, query)).unwrap();
+    }
+    drop(query_sender);
+    let (result_sender, result_receiver) = crossbeam_channel::unbounded();
+    let mut stdout = io::BufWriter::new(io::stdout());
+    thread::scope(|scope| -> tantivy::Result<()> {
+        for _ in 0..options.num_threads.max(1) {
+            let query_receiver = query_receiver.clone();
+            let result_sender = result_sender.clone();
+            let (searcher, query_parser, projection, options) =
+                (&searcher, &query_parser, &projection, &options);
+            scope.spawn(move || {
+                for (query_ord, (line, query)) in query_receiver {
+                    let result = match query {
+                        Ok(query) => {
+                            run_batch_query(searcher, query_parser, projection, options, query)
+                        }
+                        Err(error) => BatchResult::failed(line, error),
+                    };
+                    if result_sender.send((query_ord, result)).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+        drop(result_sender);
+        // The results are written in the order of the queries file, as soon as
+        // the results of all the queries before them are written.
+        let mut pending_results = BTreeMap::new();
+        let mut next_query_ord = 0;
+        for (query_ord, result) in result_receiver {
+            pending_results.insert(query_ord, result);
+            while let Some(result) = pending_results.remove(&next_query_ord) {
+                writeln!(stdout, "{}", serde_json::to_string(&result)?)?;
+                next_query_ord += 1;
+            }
+        }
+        Ok(())
+    })?;
+    stdout.flush()?;
+    Ok(())
+}
+
+fn run_batch_query(
+    searcher: &Searcher,
+    query_parser: &QueryParser,
+    projection: &FieldProjection,
+    options: &BatchOptions,
+    batch_query: BatchQuery,
+) -> BatchResult {
+    let mut query_errors = Vec::new();
+    let hits = search_batch_query(
+        searcher,
+        query_parser,
+        projection,
+        options,
+        &batch_query,
+        &mut query_errors,
+    );
+    match hits {
+        Ok((hits, num_hits)) => BatchResult {
+            query: batch_query.query,
+            num_hits: Some(num_hits),
+            hits: Some(hits),
+            query_errors,
+            error: None,
+        },
+        Err(e) => {
+            let error = match e {
+                TantivyError::InvalidArgument(message) => message,
+                e => e.to_string(),
+            };
+            BatchResult {
+                query_errors,
+                ..BatchResult::failed(batch_query.query, error)
+            }
+        }
+    }
+}
+
+fn search_batch_query(
+    searcher: &Searcher,
+    query_parser: &QueryParser,
+    projection: &FieldProjection,
+    options: &BatchOptions,
+    batch_query: &BatchQuery,
+    query_errors: &mut Vec<String>,
+) -> tantivy::Result<(Vec<Hit>, usize)> {
+    let limit = batch_query.limit.unwrap_or(options.limit);
+    if limit == 0 {
+        return Err(TantivyError::InvalidArgument(
+            "The limit must be greater than 0".to_string(),
+        ));
+    }
+    let offset = batch_query.offset.unwrap_or(options.offset);
+    check_top_docs_window(limit, offset).map_err(TantivyError::InvalidArgument)?;
+    let sort_by = match &batch_query.sort_by {
+        Some(sort_by) => Some(
+            sort_by
+                .parse::<SortBy>()
+                .map_err(TantivyError::InvalidArgument)?,
+        ),
+        None => None,
+    };
+    let query = if batch_query.lenient.unwrap_or(options.lenient) {
+        let (query, errors) = query_parser.parse_query_lenient(&batch_query.query);
+        query_errors.extend(errors.iter().map(ToString::to_string));
+        query
+    } else {
+        query_parser
+            .parse_query(&batch_query.query)
+            .map_err(|e| TantivyError::InvalidArgument(format!("Invalid query: {}", e)))?
+    };
+    let (top_docs, num_hits) = search_top_docs(
+        searcher,
+        &query,
+        sort_by.as_ref().or(options.sort_by.as_ref()),
+        limit,
+        offset,
+        Count,
+    )?;
+    let hits = top_docs
+        .into_iter()
+        .map(|(score, sort_value, doc_address)| {
+            let doc: Option<TantivyDocument> = if projection.needs_stored_doc() {
+                Some(searcher.doc(doc_address)?)
+            } else {
+                None
+            };
+            Ok(Hit {
+                index: None,
+                score,
+                sort_value,
+                doc_address,
+                doc: projection.named_doc(searcher, doc_address, doc.as_ref())?,
+                snippets: BTreeMap::new(),
+                explanation: None,
+            })
+        })
+        .collect::<tantivy::Result<_>>()?;
+    Ok((hits, num_hits))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hits_are_only_missing_from_failed_queries() {
+        let succeeded = BatchResult {
+            query: "nothing".to_string(),
+            num_hits: Some(0),
+            hits: Some(Vec::new()),
+            query_errors: Vec::new(),
+            error: None,
+        };
+        assert_eq!(
+            serde_json::to_value(succeeded).unwrap(),
+            serde_json::json!({"query": "nothing", "num_hits": 0, "hits": []})
+        );
+        let failed = BatchResult::failed("title:(".to_string(), "Invalid query".to_string());
+        assert_eq!(
+            serde_json::to_value(failed).unwrap(),
+            serde_json::json!({"query": "title:(", "error": "Invalid query"})
+        );
+    }
+
+    #[test]
+    fn parse_query_lines() {
+        assert!(parse_query_line("  ").is_none());
+        assert_eq!(
+            parse_query_line("barack obama").unwrap().unwrap().query,
+            "barack obama"
+        );
+        assert_eq!(
+            parse_query_line(r#"{"query": "obama", "limit": 3, "sort_by": "views"}"#)
+                .unwrap()
+                .unwrap(),
+            BatchQuery {
+                query: "obama".to_string(),
+                limit: Some(3),
+                offset: None,
+                sort_by: Some("views".to_string()),
+                lenient: None,
+            }
+        );
+        assert!(parse_query_line(r#"{"q": "obama"}"#).unwrap().is_err());
+    }
+}
@@ -1,3 +1,5 @@
+use crate::commands::query_settings::{index_query_parser, QuerySettings};
+use crate::commands::tokenizers::open_index;
 use crate::timer::TimerTree;
 use clap::ArgMatches;
 use std::fs::File;
@@ -7,9 +9,6 @@ use std::io::BufReader;
 use std::path::Path;
 use std::path::PathBuf;
 use tantivy::collector::{Count, TopDocs};
-use tantivy::query::QueryParser;
-use tantivy::schema::{Field, Schema};
-use tantivy::Index;
 use tantivy::TantivyDocument;
 
 pub fn run_bench_cli(matches: &ArgMatches) -> Result<(), String> {
@@ -17,15 +16,8 @@ pub fn run_bench_cli(matches: &ArgMatches) -> Result<(), String> {
     let queries_path = PathBuf::from(matches.get_one::<String>("queries").unwrap()); // the unwrap is safe as long as it is comming from the main cli.
     let num_repeat: usize = *ArgMatches::get_one(matches, "num_repeat")
         .expect("Failed to read num_repeat argument as an integer");
-    run_bench(&index_path, &queries_path, num_repeat).map_err(From::from)
-}
-
-fn extract_search_fields(schema: &Schema) -> Vec<Field> {
-    schema
-        .fields()
-        .filter(|&(_, field_entry)| field_entry.is_indexed())
-        .map(|(field, _)| field)
-        .collect()
+    let query_settings = QuerySettings::from_matches(matches)?;
+    run_bench(&index_path, &queries_path, num_repeat, query_settings)
 }
 
 fn read_query_file(query_path: &Path) -> io::Result<Vec<String>> {
@@ -38,25 +30,25 @@ fn read_query_file(query_path: &Path) -> io::Result<Vec<String>> {
     Ok(queries)
 }
 
-fn run_bench(index_path: &Path, query_filepath: &Path, num_repeat: usize) -> Result<(), String> {
+fn run_bench(
+    index_path: &Path,
+    query_filepath: &Path,
+    num_repeat: usize,
+    query_settings: QuerySettings,
+) -> Result<(), String> {
     println!("Index : {:?}", index_path);
     println!("Query : {:?}", query_filepath);
     println!("-------------------------------\n\n\n");
 
-    let index =
-        Index::open_in_dir(index_path).map_err(|e| format!("Failed to open index.\n{:?}", e))?;
+    let index = open_index(index_path).map_err(|e| format!("Failed to open index.\n{:?}", e))?;
     let searcher = index
         .reader()
         .map_err(|err| format!("{:?}", err))?
         .searcher();
-    let default_search_fields: Vec<Field> = extract_search_fields(&index.schema());
     let queries = read_query_file(query_filepath)
         .map_err(|e| format!("Failed reading the query file:  {}", e))?;
-    let query_parser = QueryParser::new(
-        index.schema(),
-        default_search_fields,
-        index.tokenizers().clone(),
-    );
+    let query_parser =
+        index_query_parser(&index, index_path, query_settings).map_err(|e| format!("{:?}", e))?;
 
     println!("SEARCH\n");
     println!("query\tnum hits\ttime in microsecs");
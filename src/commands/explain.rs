@@ -0,0 +1,71 @@
+use serde_json::Value;
+use std::fmt::Write;
+use tantivy::query::Explanation;
+
+/// How score explanations are written by `search`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExplainFormat {
+    /// In each hit, as a JSON object.
+    Json,
+    /// As an indented text tree, after each hit.
+    Tree,
+}
+
+/// Renders an explanation as a text tree, one node per line, children
+/// indented below their parent.
+pub fn explanation_tree(explanation: &Explanation) -> String {
+    let mut tree = String::new();
+    // `Explanation` only exposes its nodes through its serialization.
+    let explanation_json = serde_json::to_value(explanation).unwrap();
+    write_explanation_node(&mut tree, &explanation_json, 0);
+    tree
+}
+
+fn write_explanation_node(tree: &mut String, node: &Value, depth: usize) {
+    let indent = "  ".repeat(depth);
+    let value = node
+        .get("value")
+        .and_then(Value::as_f64)
+        .unwrap_or_default();
+    let description = node
+        .get("description")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    writeln!(tree, "{}{} {}", indent, value as f32, description).unwrap();
+    for context in node
+        .get("context")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+    {
+        writeln!(tree, "{}  ({})", indent, context).unwrap();
+    }
+    for detail in node
+        .get("details")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        write_explanation_node(tree, detail, depth + 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn explanation_tree_is_indented() {
+        let mut tf = Explanation::new("TermQuery, product of...", 0.5);
+        tf.add_const("idf", 0.25);
+        tf.add_context("Term=body:paris".to_string());
+        let mut explanation = Explanation::new("BooleanClause. sum of ...", 0.5);
+        explanation.add_detail(tf);
+        assert_eq!(
+            explanation_tree(&explanation),
+            "0.5 BooleanClause. sum of ...\n  0.5 TermQuery, product of...\n    \
+             (Term=body:paris)\n    0.25 idf\n"
+        );
+    }
+}
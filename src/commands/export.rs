@@ -0,0 +1,138 @@
+use clap::ArgMatches;
+use std::io::{self, ErrorKind, Write};
+use std::path::Path;
+use std::path::PathBuf;
+use tantivy::query::{EnableScoring, Query};
+use tantivy::schema::Schema;
+use tantivy::{Document, Searcher, TantivyDocument, TantivyError, TERMINATED};
+
+use crate::commands::output::{csv_cell, flatten_named_doc, write_csv_record};
+use crate::commands::query_settings::load_query_settings;
+use crate::commands::tokenizers::open_index;
+
+#[derive(Clone, Copy)]
+enum ExportFormat {
+    Ndjson,
+    Csv,
+}
+
+pub fn run_export_cli(matches: &ArgMatches) -> Result<(), String> {
+    let index_directory = PathBuf::from(matches.get_one::<String>("index").unwrap());
+    let query = matches.get_one::<String>("query").map(String::as_str);
+    let fields: Option<Vec<String>> = matches.get_one::<String>("fields").map(|fields| {
+        fields
+            .split(',')
+            .map(|field| field.trim().to_string())
+            .collect()
+    });
+    let format = match matches.get_one::<String>("format").unwrap().as_str() {
+        "csv" => ExportFormat::Csv,
+        _ => ExportFormat::Ndjson,
+    };
+    match run_export(&index_directory, query, fields, format) {
+        Err(TantivyError::IoError(e)) if e.kind() == ErrorKind::BrokenPipe => Ok(()),
+        res => res.map_err(|e| format!("{:?}", e)),
+    }
+}
+
+/// Calls `f` on every live document matching `query`, or on every live
+/// document if there is no query, in segment order.
+pub fn for_each_stored_document<F>(
+    searcher: &Searcher,
+    query: Option<&dyn Query>,
+    mut f: F,
+) -> tantivy::Result<()>
+where
+    F: FnMut(TantivyDocument) -> tantivy::Result<()>,
+{
+    let weight = query
+        .map(|query| query.weight(EnableScoring::disabled_from_searcher(searcher)))
+        .transpose()?;
+    for segment_reader in searcher.segment_readers() {
+        let store_reader = segment_reader.get_store_reader(100)?;
+        match &weight {
+            Some(weight) => {
+                let mut scorer = weight.scorer(segment_reader, 1.0)?;
+                while scorer.doc() != TERMINATED {
+                    let doc_id = scorer.doc();
+                    if !segment_reader.is_deleted(doc_id) {
+                        f(store_reader.get(doc_id)?)?;
+                    }
+                    scorer.advance();
+                }
+            }
+            None => {
+                for doc_res in store_reader.iter(segment_reader.alive_bitset()) {
+                    f(doc_res?)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the requested fields, or all the stored fields.
+fn exported_fields(schema: &Schema, fields: Option<Vec<String>>) -> tantivy::Result<Vec<String>> {
+    let Some(fields) = fields else {
+        return Ok(schema
+            .fields()
+            .filter(|(_, field_entry)| field_entry.is_stored())
+            .map(|(_, field_entry)| field_entry.name().to_string())
+            .collect());
+    };
+    for field_name in &fields {
+        let field = schema.get_field(field_name)?;
+        if !schema.get_field_entry(field).is_stored() {
+            return Err(TantivyError::InvalidArgument(format!(
+                "Field `{}` is not stored and cannot be exported",
+                field_name
+            )));
+        }
+    }
+    Ok(fields)
+}
+
+fn run_export(
+    directory: &Path,
+    query: Option<&str>,
+    fields: Option<Vec<String>>,
+    format: ExportFormat,
+) -> tantivy::Result<()> {
+    let index = open_index(directory)?;
+    let schema = index.schema();
+    let field_names = exported_fields(&schema, fields)?;
+    let query = query
+        .map(|query| {
+            let query_parser = load_query_settings(directory)?.query_parser(&index)?;
+            query_parser.parse_query(query).map_err(TantivyError::from)
+        })
+        .transpose()?;
+    let searcher = index.reader()?.searcher();
+
+    let mut stdout = io::BufWriter::new(io::stdout());
+    if let ExportFormat::Csv = format {
+        write_csv_record(&mut stdout, &field_names)?;
+    }
+    for_each_stored_document(&searcher, query.as_deref(), |doc| {
+        let mut named_doc = doc.to_named_doc(&schema);
+        named_doc
+            .0
+            .retain(|field_name, _| field_names.contains(field_name));
+        let doc_json = flatten_named_doc(named_doc);
+        match format {
+            ExportFormat::Ndjson => {
+                writeln!(stdout, "{}", serde_json::to_string(&doc_json).unwrap())?;
+            }
+            ExportFormat::Csv => {
+                let cells: Vec<String> = field_names
+                    .iter()
+                    .map(|field_name| csv_cell(doc_json.get(field_name)))
+                    .collect();
+                write_csv_record(&mut stdout, &cells)?;
+            }
+        }
+        Ok(())
+    })?;
+    stdout.flush()?;
+    Ok(())
+}
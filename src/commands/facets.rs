@@ -0,0 +1,181 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::io::Write;
+use tantivy::collector::{FacetCollector, FacetCounts, FruitHandle, MultiCollector, MultiFruit};
+use tantivy::schema::{Facet, FieldType, Schema};
+use tantivy::TantivyError;
+
+use crate::commands::output::write_table;
+
+/// Counts of the children of each requested facet path, keyed by the path as
+/// it was requested.
+pub type FacetCountsByPath = BTreeMap<String, BTreeMap<String, u64>>;
+
+/// A facet path whose children are counted.
+struct FacetPath {
+    spec: String,
+    field_name: String,
+    facet: Facet,
+}
+
+/// Facet paths whose children are counted, parsed from `[field:]/path`.
+///
+/// The field can be left out if the schema has a single facet field.
+pub struct FacetPaths(Vec<FacetPath>);
+
+impl FacetPaths {
+    pub fn parse(schema: &Schema, specs: &[String]) -> tantivy::Result<FacetPaths> {
+        let facet_fields: Vec<&str> = schema
+            .fields()
+            .filter(|(_, field_entry)| matches!(field_entry.field_type(), FieldType::Facet(_)))
+            .map(|(_, field_entry)| field_entry.name())
+            .collect();
+        let mut paths: Vec<FacetPath> = Vec::new();
+        for spec in specs {
+            let (field_name, path) = match spec.split_once(':') {
+                Some((field_name, path)) if !spec.starts_with('/') => (field_name, path),
+                _ => match facet_fields.as_slice() {
+                    [field_name] => (*field_name, spec.as_str()),
+                    _ => {
+                        return Err(TantivyError::InvalidArgument(format!(
+                            "Facet `{}` must name its field, as `field:/path`, since the index \
+                             has {} facet fields",
+                            spec,
+                            facet_fields.len()
+                        )))
+                    }
+                },
+            };
+            if !facet_fields.contains(&field_name) {
+                return Err(TantivyError::InvalidArgument(format!(
+                    "Field `{}` is not a facet field",
+                    field_name
+                )));
+            }
+            let facet = Facet::from_text(path).map_err(|e| {
+                TantivyError::InvalidArgument(format!("Invalid facet `{}`: {}", spec, e))
+            })?;
+            if !paths.iter().any(|other| other.spec == *spec) {
+                paths.push(FacetPath {
+                    spec: spec.clone(),
+                    field_name: field_name.to_string(),
+                    facet,
+                });
+            }
+        }
+        Ok(FacetPaths(paths))
+    }
+
+    /// Returns a collector counting the children of each path, `None` if there
+    /// are no paths, and the handles reading the counts from its fruit.
+    ///
+    /// Each path gets its own facet collector, since a facet collector cannot
+    /// count a path and one of its descendants.
+    pub fn collector(&self) -> (Option<MultiCollector<'static>>, FacetHandles) {
+        if self.0.is_empty() {
+            return (None, FacetHandles(Vec::new()));
+        }
+        let mut multi_collector = MultiCollector::new();
+        let handles = self
+            .0
+            .iter()
+            .map(|path| {
+                let mut facet_collector = FacetCollector::for_field(&path.field_name);
+                facet_collector.add_facet(path.facet.clone());
+                let handle = multi_collector.add_collector(facet_collector);
+                (path.spec.clone(), path.facet.clone(), handle)
+            })
+            .collect();
+        (Some(multi_collector), FacetHandles(handles))
+    }
+}
+
+/// Reads the facet counts from the fruit of a `FacetPaths` collector.
+pub struct FacetHandles(Vec<(String, Facet, FruitHandle<FacetCounts>)>);
+
+impl FacetHandles {
+    pub fn counts(self, fruits: Option<MultiFruit>) -> FacetCountsByPath {
+        let Some(mut fruits) = fruits else {
+            return FacetCountsByPath::new();
+        };
+        self.0
+            .into_iter()
+            .map(|(spec, facet, handle)| {
+                let facet_counts = handle.extract(&mut fruits);
+                let children = facet_counts
+                    .get(facet)
+                    .map(|(child, count)| (child.to_string(), count))
+                    .collect();
+                (spec, children)
+            })
+            .collect()
+    }
+}
+
+/// Adds the facet counts of another index to `counts`.
+pub fn merge_facet_counts(counts: &mut FacetCountsByPath, other: FacetCountsByPath) {
+    for (spec, children) in other {
+        let merged_children = counts.entry(spec).or_default();
+        for (child, count) in children {
+            *merged_children.entry(child).or_default() += count;
+        }
+    }
+}
+
+/// Writes the children of each facet path and their counts as text tables.
+pub fn write_facet_tables<W: Write>(wrt: &mut W, counts: &FacetCountsByPath) -> io::Result<()> {
+    let header = vec!["facet".to_string(), "count".to_string()];
+    for (spec, children) in counts {
+        let rows: Vec<Vec<String>> = children
+            .iter()
+            .map(|(child, count)| vec![child.clone(), count.to_string()])
+            .collect();
+        writeln!(wrt, "{}", spec)?;
+        write_table(wrt, &header, &rows)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_index::create_index;
+    use tantivy::doc;
+    use tantivy::query::AllQuery;
+    use tantivy::schema::{FacetOptions, STRING};
+
+    fn test_schema() -> Schema {
+        let mut schema_builder = Schema::builder();
+        schema_builder.add_facet_field("category", FacetOptions::default());
+        schema_builder.add_text_field("title", STRING);
+        schema_builder.build()
+    }
+
+    #[test]
+    fn count_facet_children() {
+        let schema = test_schema();
+        let category = schema.get_field("category").unwrap();
+        let docs = ["/books/novels", "/books/comics", "/books/novels", "/music"]
+            .map(|path| doc!(category => Facet::from(path)));
+        let searcher = create_index(schema.clone(), docs)
+            .reader()
+            .unwrap()
+            .searcher();
+
+        let specs = vec!["/".to_string(), "category:/books".to_string()];
+        let facet_paths = FacetPaths::parse(&schema, &specs).unwrap();
+        let (collector, handles) = facet_paths.collector();
+        let counts = handles.counts(searcher.search(&AllQuery, &collector).unwrap());
+        assert_eq!(counts["/"]["/books"], 3);
+        assert_eq!(counts["/"]["/music"], 1);
+        assert_eq!(counts["category:/books"]["/books/novels"], 2);
+        assert_eq!(counts["category:/books"]["/books/comics"], 1);
+    }
+
+    #[test]
+    fn invalid_facet_paths() {
+        let schema = test_schema();
+        assert!(FacetPaths::parse(&schema, &["title:/books".to_string()]).is_err());
+        assert!(FacetPaths::parse(&schema, &["category:books".to_string()]).is_err());
+    }
+}
@@ -6,7 +6,10 @@ use std::io;
 use std::io::BufRead;
 use std::io::BufReader;
 use std::io::Read;
+use std::path::Path;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::thread;
 use std::time::Instant;
 use tantivy::merge_policy::NoMergePolicy;
@@ -16,6 +19,7 @@ use tantivy::IndexWriter;
 use tantivy::TantivyDocument;
 
 use crate::commands::merge::run_merge;
+use crate::commands::tokenizers::open_index;
 
 pub fn run_index_cli(argmatch: &ArgMatches) -> Result<(), String> {
     let index_directory = PathBuf::from(argmatch.get_one::<String>("index").unwrap());
@@ -53,10 +57,8 @@ fn run_index(
     no_merge: bool,
     force_merge: bool,
 ) -> tantivy::Result<()> {
-    let index = Index::open_in_dir(&directory)?;
-    let schema = index.schema();
+    let index = open_index(&directory)?;
     let (line_sender, line_receiver) = crossbeam_channel::bounded(100);
-    let (doc_sender, doc_receiver) = crossbeam_channel::bounded(100);
 
     thread::spawn(move || {
         let articles = document_source.read().unwrap();
@@ -66,12 +68,46 @@ fn run_index(
         }
     });
 
+    index_json_lines(
+        &index,
+        &directory,
+        line_receiver,
+        buffer_size_per_thread,
+        num_threads,
+        no_merge,
+        force_merge,
+        |_| Ok(()),
+    )
+}
+
+/// Parses the JSON documents received on `line_receiver` and indexes them
+/// until the channel is closed.
+///
+/// `before_commit` is called once the channel is closed, with the number of
+/// documents which failed to parse: the documents are only committed if it
+/// succeeds, and rolled back otherwise.
+#[allow(clippy::too_many_arguments)]
+pub fn index_json_lines(
+    index: &Index,
+    directory: &Path,
+    line_receiver: crossbeam_channel::Receiver<String>,
+    buffer_size_per_thread: usize,
+    num_threads: usize,
+    no_merge: bool,
+    force_merge: bool,
+    before_commit: impl FnOnce(usize) -> tantivy::Result<()>,
+) -> tantivy::Result<()> {
+    let schema = index.schema();
+    let num_rejected_docs = Arc::new(AtomicUsize::new(0));
+    let (doc_sender, doc_receiver) = crossbeam_channel::bounded(100);
+
     let num_threads_to_parse_json = cmp::max(1, num_threads / 4);
     log::info!("Using {} threads to parse json", num_threads_to_parse_json);
     for _ in 0..num_threads_to_parse_json {
         let schema_clone = schema.clone();
         let doc_sender_clone = doc_sender.clone();
         let line_receiver_clone = line_receiver.clone();
+        let num_rejected_docs_clone = num_rejected_docs.clone();
         thread::spawn(move || {
             for doc_str in line_receiver_clone {
                 match TantivyDocument::parse_json(&schema_clone, &doc_str) {
@@ -80,6 +116,7 @@ fn run_index(
                     }
                     Err(err) => {
                         println!("Failed to add document doc {:?}", err);
+                        num_rejected_docs_clone.fetch_add(1, Ordering::Relaxed);
                     }
                 }
             }
@@ -98,7 +135,11 @@ fn run_index(
     }
 
     let start_overall = Instant::now();
-    let index_result = index_documents(&mut index_writer, doc_receiver);
+    // The parsing threads are done once `doc_receiver` is exhausted, so that
+    // the count of rejected documents is final when `before_commit` is called.
+    let index_result = index_documents(&mut index_writer, doc_receiver, || {
+        before_commit(num_rejected_docs.load(Ordering::Relaxed))
+    });
     {
         let duration = start_overall - Instant::now();
         log::info!("Indexing the documents took {} s", duration.as_secs());
@@ -119,7 +160,7 @@ fn run_index(
 
             if force_merge {
                 println!("force_merge");
-                run_merge(directory)?;
+                run_merge(directory.to_path_buf())?;
             }
 
             let elapsed_after_merge = Instant::now() - start_overall;
@@ -155,6 +196,7 @@ struct IndexResult {
 fn index_documents<D: Document>(
     index_writer: &mut IndexWriter<D>,
     doc_receiver: crossbeam_channel::Receiver<(D, usize)>,
+    before_commit: impl FnOnce() -> tantivy::Result<()>,
 ) -> tantivy::Result<IndexResult> {
     let mut num_docs_total = 0;
     let mut num_docs = 0;
@@ -188,6 +230,7 @@ fn index_documents<D: Document>(
             }
         }
     }
+    before_commit()?;
     let res = index_writer.commit()?;
 
     Ok(IndexResult {
@@ -212,3 +255,54 @@ impl DocumentSource {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tantivy::schema::{Schema, STORED, TEXT};
+    use tantivy::TantivyError;
+
+    fn index_lines(
+        index: &Index,
+        lines: &[&str],
+        before_commit: impl FnOnce(usize) -> tantivy::Result<()>,
+    ) -> tantivy::Result<()> {
+        let (line_sender, line_receiver) = crossbeam_channel::bounded(100);
+        for line in lines {
+            line_sender.send(line.to_string()).unwrap();
+        }
+        drop(line_sender);
+        index_json_lines(
+            index,
+            Path::new("unused"),
+            line_receiver,
+            15_000_000,
+            1,
+            false,
+            false,
+            before_commit,
+        )
+    }
+
+    #[test]
+    fn index_json_lines_commits_only_if_before_commit_succeeds() {
+        let mut schema_builder = Schema::builder();
+        schema_builder.add_text_field("title", TEXT | STORED);
+        let index = Index::create_in_ram(schema_builder.build());
+        let num_docs = || index.reader().unwrap().searcher().num_docs();
+        let lines = [r#"{"title": "first"}"#, r#"{"title": "second"}"#, "{"];
+
+        let failure = |_| Err(TantivyError::ErrorInThread("reader failed".to_string()));
+        assert!(index_lines(&index, &lines, failure).is_err());
+        assert_eq!(num_docs(), 0);
+
+        let mut num_rejected_docs = None;
+        index_lines(&index, &lines, |num_rejected| {
+            num_rejected_docs = Some(num_rejected);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(num_rejected_docs, Some(1));
+        assert_eq!(num_docs(), 2);
+    }
+}
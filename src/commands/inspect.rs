@@ -4,7 +4,8 @@ use std::path::Path;
 use std::path::PathBuf;
 use tantivy::schema::Schema;
 use tantivy::space_usage::PerFieldSpaceUsage;
-use tantivy::Index;
+
+use crate::commands::tokenizers::open_index;
 
 pub fn run_inspect_cli(matches: &ArgMatches) -> Result<(), String> {
     let index_directory = PathBuf::from(matches.get_one::<String>("index").unwrap());
@@ -12,7 +13,7 @@ pub fn run_inspect_cli(matches: &ArgMatches) -> Result<(), String> {
 }
 
 fn run_inspect(directory: &Path) -> tantivy::Result<()> {
-    let index = Index::open_in_dir(directory)?;
+    let index = open_index(directory)?;
     let schema = index.schema();
     let searcher = index.reader()?.searcher();
     let segments = searcher.segment_readers();
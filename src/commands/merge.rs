@@ -1,6 +1,8 @@
 use clap::ArgMatches;
 use std::path::PathBuf;
-use tantivy::{Index, TantivyDocument};
+use tantivy::TantivyDocument;
+
+use crate::commands::tokenizers::open_index;
 
 const HEAP_SIZE: usize = 300_000_000;
 
@@ -16,7 +18,7 @@ pub fn run_merge_cli(argmatch: &ArgMatches) -> Result<(), String> {
 }
 
 pub fn run_merge(path: PathBuf) -> tantivy::Result<()> {
-    let index = Index::open_in_dir(&path)?;
+    let index = open_index(&path)?;
     let segments = index.searchable_segment_ids()?;
     let segment_meta = index
         .writer::<TantivyDocument>(HEAP_SIZE)?
@@ -24,7 +26,7 @@ pub fn run_merge(path: PathBuf) -> tantivy::Result<()> {
         .wait()?;
     println!("Merge finished with segment meta {:?}", segment_meta);
     println!("Garbage collect irrelevant segments.");
-    Index::open_in_dir(&path)?
+    open_index(&path)?
         .writer_with_num_threads::<TantivyDocument>(1, 40_000_000)?
         .garbage_collect_files()
         .wait()?;
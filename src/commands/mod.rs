@@ -1,16 +1,37 @@
+mod aggregation;
+mod analyze;
+mod batch;
 mod bench;
+mod explain;
+mod export;
+mod facets;
 mod index;
 mod infer_schema;
 mod inspect;
 mod merge;
+mod more_like_this;
+mod multi_index;
 mod new;
+mod output;
+mod projection;
+mod query_settings;
+mod reindex;
+mod schema;
 mod search;
 mod serve;
+mod snippets;
+#[cfg(test)]
+mod test_index;
+mod tokenizers;
 
+pub use self::analyze::run_analyze_cli;
 pub use self::bench::run_bench_cli;
+pub use self::export::run_export_cli;
 pub use self::index::run_index_cli;
 pub use self::inspect::run_inspect_cli;
 pub use self::merge::run_merge_cli;
 pub use self::new::run_new_cli;
+pub use self::reindex::run_reindex_cli;
+pub use self::schema::run_schema_cli;
 pub use self::search::run_search_cli;
 pub use self::serve::run_serve_cli;
@@ -0,0 +1,238 @@
+use tantivy::query::MoreLikeThisQuery;
+use tantivy::schema::{Field, FieldType, OwnedValue, Schema};
+use tantivy::{DocAddress, Document, Searcher, TantivyDocument, TantivyError};
+
+use crate::commands::search::RankedDoc;
+
+/// What the documents found by a more-like-this query are like.
+pub enum LikeTarget {
+    /// A document of the index, as `segment:doc`.
+    Doc(DocAddress),
+    Text(String),
+}
+
+/// Parses a document address given as `segment:doc`, the `segment_ord` and
+/// `doc_id` of a hit.
+pub fn parse_doc_address(doc_address: &str) -> Result<DocAddress, String> {
+    let invalid_doc_address =
+        || format!("Invalid document `{}`, expected `segment:doc`", doc_address);
+    let (segment_ord, doc_id) = doc_address
+        .split_once(':')
+        .ok_or_else(invalid_doc_address)?;
+    Ok(DocAddress::new(
+        segment_ord
+            .trim()
+            .parse()
+            .map_err(|_| invalid_doc_address())?,
+        doc_id.trim().parse().map_err(|_| invalid_doc_address())?,
+    ))
+}
+
+/// A more-like-this query, finding the documents sharing the most
+/// distinctive terms of a document or a text.
+///
+/// The options left unset take the defaults of tantivy's `MoreLikeThisQuery`.
+pub struct LikeQuery {
+    pub target: LikeTarget,
+    /// Text fields the terms are drawn from and searched in, all the indexed
+    /// text fields if empty.
+    pub fields: Vec<String>,
+    /// Terms found in fewer documents are ignored.
+    pub min_doc_frequency: Option<u64>,
+    /// Terms occurring fewer times in the document or the text are ignored.
+    pub min_term_frequency: Option<usize>,
+    pub max_query_terms: Option<usize>,
+}
+
+impl LikeQuery {
+    /// The liked document, if any. It matches its own query best, so it is
+    /// left out of the hits.
+    pub fn liked_doc(&self) -> Option<DocAddress> {
+        match self.target {
+            LikeTarget::Doc(doc_address) => Some(doc_address),
+            LikeTarget::Text(_) => None,
+        }
+    }
+
+    /// Removes the liked document from `top_docs`, the best documents of the
+    /// index it was read from, and returns whether it was there.
+    pub fn remove_liked_doc(&self, top_docs: &mut Vec<RankedDoc>) -> bool {
+        let num_top_docs = top_docs.len();
+        top_docs.retain(|(_, _, doc_address)| Some(*doc_address) != self.liked_doc());
+        top_docs.len() < num_top_docs
+    }
+
+    /// Builds the query. A liked document is read with `searcher`.
+    pub fn query(&self, searcher: &Searcher) -> tantivy::Result<MoreLikeThisQuery> {
+        let fields = like_fields(searcher.schema(), &self.fields)?;
+        let doc_fields: Vec<(Field, Vec<OwnedValue>)> = match &self.target {
+            LikeTarget::Doc(doc_address) => {
+                let segment_readers = searcher.segment_readers();
+                let is_valid = segment_readers
+                    .get(doc_address.segment_ord as usize)
+                    .is_some_and(|segment_reader| {
+                        doc_address.doc_id < segment_reader.max_doc()
+                            && !segment_reader.is_deleted(doc_address.doc_id)
+                    });
+                if !is_valid {
+                    return Err(TantivyError::InvalidArgument(format!(
+                        "There is no document {}:{}",
+                        doc_address.segment_ord, doc_address.doc_id
+                    )));
+                }
+                let doc: TantivyDocument = searcher.doc(*doc_address)?;
+                fields
+                    .into_iter()
+                    .map(|field| {
+                        let values = doc
+                            .iter_fields_and_values()
+                            .filter(|(value_field, _)| *value_field == field)
+                            .map(|(_, value)| OwnedValue::from(value))
+                            .collect();
+                        (field, values)
+                    })
+                    .collect()
+            }
+            LikeTarget::Text(text) => fields
+                .into_iter()
+                .map(|field| (field, vec![OwnedValue::Str(text.clone())]))
+                .collect(),
+        };
+        let mut builder = MoreLikeThisQuery::builder();
+        if let Some(min_doc_frequency) = self.min_doc_frequency {
+            builder = builder.with_min_doc_frequency(min_doc_frequency);
+        }
+        if let Some(min_term_frequency) = self.min_term_frequency {
+            builder = builder.with_min_term_frequency(min_term_frequency);
+        }
+        if let Some(max_query_terms) = self.max_query_terms {
+            builder = builder.with_max_query_terms(max_query_terms);
+        }
+        Ok(builder.with_document_fields(doc_fields))
+    }
+}
+
+/// Returns the fields named `field_names`, which must be indexed text
+/// fields, or all the indexed text fields if there are no names.
+fn like_fields(schema: &Schema, field_names: &[String]) -> tantivy::Result<Vec<Field>> {
+    let is_indexed_text = |field: Field| match schema.get_field_entry(field).field_type() {
+        FieldType::Str(options) => options.get_indexing_options().is_some(),
+        _ => false,
+    };
+    if field_names.is_empty() {
+        return Ok(schema
+            .fields()
+            .map(|(field, _)| field)
+            .filter(|field| is_indexed_text(*field))
+            .collect());
+    }
+    field_names
+        .iter()
+        .map(|field_name| {
+            let field = schema.get_field(field_name)?;
+            if !is_indexed_text(field) {
+                return Err(TantivyError::InvalidArgument(format!(
+                    "Field `{}` is not an indexed text field",
+                    field_name
+                )));
+            }
+            Ok(field)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_index::create_index;
+    use tantivy::collector::TopDocs;
+    use tantivy::doc;
+    use tantivy::schema::{FAST, STORED, TEXT};
+
+    fn test_searcher() -> Searcher {
+        let mut schema_builder = Schema::builder();
+        let body = schema_builder.add_text_field("body", TEXT | STORED);
+        schema_builder.add_u64_field("views", FAST);
+        let docs = [
+            "the old man and the sea",
+            "an old man sailing on the sea",
+            "send this message to alice",
+        ]
+        .map(|text| doc!(body => text));
+        create_index(schema_builder.build(), docs)
+            .reader()
+            .unwrap()
+            .searcher()
+    }
+
+    fn like_query(target: LikeTarget) -> LikeQuery {
+        LikeQuery {
+            target,
+            fields: Vec::new(),
+            min_doc_frequency: Some(1),
+            min_term_frequency: Some(1),
+            max_query_terms: None,
+        }
+    }
+
+    fn top_docs(searcher: &Searcher, like_query: LikeQuery) -> Vec<DocAddress> {
+        let query = like_query.query(searcher).unwrap();
+        let mut top_docs: Vec<RankedDoc> = searcher
+            .search(&query, &TopDocs::with_limit(3).order_by_score())
+            .unwrap()
+            .into_iter()
+            .map(|(score, doc_address)| (Some(score), None, doc_address))
+            .collect();
+        like_query.remove_liked_doc(&mut top_docs);
+        top_docs
+            .into_iter()
+            .map(|(_, _, doc_address)| doc_address)
+            .collect()
+    }
+
+    #[test]
+    fn parse_doc_addresses() {
+        assert_eq!(parse_doc_address("1:42"), Ok(DocAddress::new(1, 42)));
+        assert_eq!(parse_doc_address(" 0 : 7 "), Ok(DocAddress::new(0, 7)));
+        assert!(parse_doc_address("42").is_err());
+        assert!(parse_doc_address("1:x").is_err());
+        assert!(parse_doc_address("-1:2").is_err());
+    }
+
+    #[test]
+    fn like_doc() {
+        let searcher = test_searcher();
+        assert_eq!(
+            top_docs(
+                &searcher,
+                like_query(LikeTarget::Doc(DocAddress::new(0, 0)))
+            ),
+            vec![DocAddress::new(0, 1)]
+        );
+        assert!(like_query(LikeTarget::Doc(DocAddress::new(0, 3)))
+            .query(&searcher)
+            .is_err());
+        assert!(like_query(LikeTarget::Doc(DocAddress::new(1, 0)))
+            .query(&searcher)
+            .is_err());
+    }
+
+    #[test]
+    fn like_text() {
+        let searcher = test_searcher();
+        assert_eq!(
+            top_docs(&searcher, like_query(LikeTarget::Text("alice".to_string()))),
+            vec![DocAddress::new(0, 2)]
+        );
+    }
+
+    #[test]
+    fn like_fields_must_be_indexed_text_fields() {
+        let searcher = test_searcher();
+        let mut like_views = like_query(LikeTarget::Text("alice".to_string()));
+        like_views.fields = vec!["views".to_string()];
+        assert!(like_views.query(&searcher).is_err());
+        like_views.fields = vec!["body".to_string()];
+        assert!(like_views.query(&searcher).is_ok());
+    }
+}
@@ -0,0 +1,150 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use tantivy::schema::OwnedValue;
+use tantivy::{Index, Order, TantivyError};
+
+use crate::commands::search::{RankedDoc, SortBy};
+use crate::commands::tokenizers::open_index;
+
+/// An index searched along with others.
+pub struct NamedIndex {
+    /// Name of the index in the hits: the name of its directory, or its path
+    /// if several indexes have the same directory name.
+    pub name: String,
+    pub directory: PathBuf,
+    pub index: Index,
+}
+
+/// Opens the indexes of `directories`, which must all have the same schema.
+pub fn open_indexes(directories: &[PathBuf]) -> tantivy::Result<Vec<NamedIndex>> {
+    let mut names: Vec<String> = directories
+        .iter()
+        .map(|directory| {
+            directory
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| directory.display().to_string())
+        })
+        .collect();
+    let distinct_names: HashSet<&String> = names.iter().collect();
+    if distinct_names.len() < names.len() {
+        names = directories
+            .iter()
+            .map(|directory| directory.display().to_string())
+            .collect();
+    }
+    let mut indexes: Vec<NamedIndex> = Vec::new();
+    for (directory, name) in directories.iter().zip(names) {
+        let index = open_index(directory)?;
+        if let Some(first) = indexes.first() {
+            if index.schema() != first.index.schema() {
+                return Err(TantivyError::InvalidArgument(format!(
+                    "Index `{}` does not have the same schema as index `{}`",
+                    name, first.name
+                )));
+            }
+        }
+        indexes.push(NamedIndex {
+            name,
+            directory: directory.clone(),
+            index,
+        });
+    }
+    Ok(indexes)
+}
+
+/// Merges the best ranked documents of each index, returning the `limit`
+/// best ones after skipping `offset` of them, with the ordinal of their index.
+///
+/// Each index must have returned its `offset + limit` best documents.
+pub fn merge_top_docs(
+    top_docs_per_index: Vec<Vec<RankedDoc>>,
+    sort_by: Option<&SortBy>,
+    limit: usize,
+    offset: usize,
+) -> Vec<(usize, RankedDoc)> {
+    let mut top_docs: Vec<(usize, RankedDoc)> = top_docs_per_index
+        .into_iter()
+        .enumerate()
+        .flat_map(|(index_ord, top_docs)| {
+            top_docs
+                .into_iter()
+                .map(move |ranked_doc| (index_ord, ranked_doc))
+        })
+        .collect();
+    // The sort is stable, so ties are ranked in the order of the indexes.
+    top_docs.sort_by(|(_, left), (_, right)| match sort_by {
+        Some(sort_by) => compare_sort_values(left.1.as_ref(), right.1.as_ref(), sort_by.order),
+        None => right.0.partial_cmp(&left.0).unwrap_or(Ordering::Equal),
+    });
+    top_docs.into_iter().skip(offset).take(limit).collect()
+}
+
+/// Ranks two sort values, documents without a value coming last.
+fn compare_sort_values(
+    left: Option<&OwnedValue>,
+    right: Option<&OwnedValue>,
+    order: Order,
+) -> Ordering {
+    let (left, right) = match (left, right) {
+        (Some(left), Some(right)) => (left, right),
+        (left, right) => return left.is_none().cmp(&right.is_none()),
+    };
+    let ordering = match (left, right) {
+        (OwnedValue::U64(left), OwnedValue::U64(right)) => left.cmp(right),
+        (OwnedValue::I64(left), OwnedValue::I64(right)) => left.cmp(right),
+        (OwnedValue::F64(left), OwnedValue::F64(right)) => {
+            left.partial_cmp(right).unwrap_or(Ordering::Equal)
+        }
+        (OwnedValue::Date(left), OwnedValue::Date(right)) => left.cmp(right),
+        _ => Ordering::Equal,
+    };
+    match order {
+        Order::Asc => ordering,
+        Order::Desc => ordering.reverse(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tantivy::DocAddress;
+
+    #[test]
+    fn merge_top_docs_by_score_and_by_sort_value() {
+        let doc = |doc_id| DocAddress::new(0, doc_id);
+        let top_docs_per_index = vec![
+            vec![(Some(3.0), None, doc(0)), (Some(1.0), None, doc(1))],
+            vec![(Some(2.0), None, doc(0)), (Some(1.0), None, doc(1))],
+        ];
+        assert_eq!(
+            merge_top_docs(top_docs_per_index, None, 3, 1),
+            vec![
+                (1, (Some(2.0), None, doc(0))),
+                (0, (Some(1.0), None, doc(1))),
+                (1, (Some(1.0), None, doc(1))),
+            ]
+        );
+
+        let top_docs_per_index = vec![
+            vec![
+                (None, Some(OwnedValue::U64(5)), doc(0)),
+                (None, None, doc(1)),
+            ],
+            vec![(None, Some(OwnedValue::U64(7)), doc(0))],
+        ];
+        let sort_by = SortBy {
+            field: "views".to_string(),
+            order: Order::Asc,
+        };
+        assert_eq!(
+            merge_top_docs(top_docs_per_index, Some(&sort_by), 3, 0),
+            vec![
+                (0, (None, Some(OwnedValue::U64(5)), doc(0))),
+                (1, (None, Some(OwnedValue::U64(7)), doc(0))),
+                (0, (None, None, doc(1))),
+            ]
+        );
+    }
+}
@@ -8,20 +8,69 @@ use std::io;
 use std::io::Write;
 use std::path::PathBuf;
 use tantivy::schema::*;
+use tantivy::tokenizer::TokenizerManager;
 use tantivy::Index;
 
 use super::infer_schema::{infer_schema_from_ndjson, InferredField, InferredType};
+use super::schema::schema_json;
+use super::tokenizers::{
+    parse_tokenizer_configs, save_tokenizer_configs, tokenizer_manager, tokenizer_names,
+    validate_schema_tokenizers, BaseTokenizer, TokenFilterConfig, TokenizerConfig,
+    TokenizerConfigs,
+};
 
 pub fn run_new_cli(matches: &ArgMatches) -> Result<(), String> {
     let index_directory = PathBuf::from(matches.get_one::<String>("index").unwrap());
+    let tokenizer_configs = match matches.get_one::<String>("tokenizers") {
+        Some(tokenizers_file) => read_tokenizer_configs(tokenizers_file)?,
+        None => TokenizerConfigs::new(),
+    };
+    let mut tokenizers = IndexTokenizers::new(tokenizer_configs).map_err(|e| format!("{}", e))?;
     if let Some(ndjson_file) = matches.get_one::<String>("infer_from_ndjson") {
         let sample_size = *matches.get_one::<usize>("sample_size").unwrap();
-        run_new_with_inferred_schema(index_directory, PathBuf::from(ndjson_file), sample_size)
+        run_new_with_inferred_schema(
+            index_directory,
+            PathBuf::from(ndjson_file),
+            sample_size,
+            &mut tokenizers,
+        )
     } else {
-        run_new_interactive(index_directory).map_err(|e| format!("{:?}", e))
+        run_new_interactive(index_directory, &mut tokenizers).map_err(|e| format!("{:?}", e))
+    }
+}
+
+const NGRAM: &str = "ngram";
+const EDGE_NGRAM: &str = "edge_ngram";
+
+/// Tokenizers the fields of the new index may refer to.
+pub struct IndexTokenizers {
+    pub configs: TokenizerConfigs,
+    pub manager: TokenizerManager,
+}
+
+impl IndexTokenizers {
+    pub fn new(configs: TokenizerConfigs) -> tantivy::Result<IndexTokenizers> {
+        let manager = tokenizer_manager(&configs)?;
+        Ok(IndexTokenizers { configs, manager })
+    }
+
+    fn names(&self) -> Vec<String> {
+        tokenizer_names(&self.configs)
+    }
+
+    fn add(&mut self, name: &str, config: TokenizerConfig) -> tantivy::Result<()> {
+        self.manager.register(name, config.build()?);
+        self.configs.insert(name.to_string(), config);
+        Ok(())
     }
 }
 
+fn read_tokenizer_configs(tokenizers_file: &str) -> Result<TokenizerConfigs, String> {
+    let json = fs::read_to_string(tokenizers_file)
+        .map_err(|e| format!("failed to read {:?}: {:?}", tokenizers_file, e))?;
+    parse_tokenizer_configs(&json).map_err(|e| format!("{}", e))
+}
+
 fn prompt_input<P: Fn(&str) -> Result<(), String>>(prompt_text: &str, predicate: P) -> String {
     loop {
         print!(
@@ -103,20 +152,78 @@ fn prompt_field_type(msg: &str, codes: Vec<&str>) -> tantivy::schema::Type {
     }
 }
 
-fn prompt_yn(msg: &str) -> bool {
+pub fn prompt_yn(msg: &str) -> bool {
     prompt_options(msg, vec!['Y', 'N']) == 'Y'
 }
 
-fn prompt_tokenizer_or_default() -> String {
-    let tokenizer = prompt_input("Tokenizer (enter for default)", |_| Ok(()));
-    if tokenizer.trim().is_empty() {
+fn prompt_gram_size(msg: &str, min_value: usize) -> usize {
+    let predicate = |entry: &str| match entry.trim().parse::<usize>() {
+        Ok(value) if value >= min_value => Ok(()),
+        _ => Err(format!(
+            "Expected an integer greater or equal to {}",
+            min_value
+        )),
+    };
+    prompt_input(msg, predicate).trim().parse().unwrap()
+}
+
+/// Asks for the gram sizes of a n-gram tokenizer and adds it to the index tokenizers.
+///
+/// Edge n-grams only emit the prefixes of the text, which is what autocomplete needs.
+fn ask_add_ngram_tokenizer(prefix_only: bool, tokenizers: &mut IndexTokenizers) -> String {
+    let min_gram = prompt_gram_size("Minimum gram size", 1);
+    let max_gram = prompt_gram_size("Maximum gram size", min_gram);
+    let kind = if prefix_only { EDGE_NGRAM } else { NGRAM };
+    let tokenizer_name = format!("{}_{}_{}", kind, min_gram, max_gram);
+    let tokenizer_config = TokenizerConfig {
+        tokenizer: BaseTokenizer::Ngram {
+            min_gram,
+            max_gram,
+            prefix_only,
+        },
+        filters: vec![TokenFilterConfig::Lowercase],
+    };
+    tokenizers
+        .add(&tokenizer_name, tokenizer_config)
+        .expect("gram sizes are validated by the prompt");
+    tokenizer_name
+}
+
+fn prompt_tokenizer_or_default(tokenizers: &mut IndexTokenizers) -> String {
+    let mut options = tokenizers.names();
+    options.push(NGRAM.to_string());
+    options.push(EDGE_NGRAM.to_string());
+    let options = options.join("/");
+    let predicate = |entry: &str| {
+        let entry = entry.trim();
+        if entry.is_empty()
+            || entry == NGRAM
+            || entry == EDGE_NGRAM
+            || tokenizers.manager.get(entry).is_some()
+        {
+            Ok(())
+        } else {
+            Err(format!("Unknown tokenizer. Options are ({})", options))
+        }
+    };
+    let message = format!("Tokenizer, enter for default ({})", options);
+    let tokenizer = prompt_input(&message, predicate);
+    let tokenizer = tokenizer.trim();
+    if tokenizer.is_empty() {
         "default".to_string()
+    } else if tokenizers.manager.get(tokenizer).is_some() {
+        // A custom tokenizer may be named `ngram` or `edge_ngram` too.
+        tokenizer.to_string()
     } else {
-        tokenizer
+        ask_add_ngram_tokenizer(tokenizer == EDGE_NGRAM, tokenizers)
     }
 }
 
-fn ask_add_field_text(field_name: &str, schema_builder: &mut SchemaBuilder) {
+fn ask_add_field_text(
+    field_name: &str,
+    schema_builder: &mut SchemaBuilder,
+    tokenizers: &mut IndexTokenizers,
+) {
     let mut text_options = TextOptions::default();
     if prompt_yn("Should the field be stored") {
         text_options = text_options.set_stored();
@@ -129,7 +236,7 @@ fn ask_add_field_text(field_name: &str, schema_builder: &mut SchemaBuilder) {
         let mut text_indexing_options = TextFieldIndexing::default()
             .set_index_option(IndexRecordOption::Basic)
             .set_tokenizer("default");
-        let tokenizer = prompt_tokenizer_or_default();
+        let tokenizer = prompt_tokenizer_or_default(tokenizers);
 
         if prompt_yn("Should the term be tokenized?") {
             text_indexing_options = text_indexing_options.set_tokenizer(&tokenizer);
@@ -187,7 +294,11 @@ fn ask_add_num_field_with_options(
     }
 }
 
-fn ask_add_field_json(field_name: &str, schema_builder: &mut SchemaBuilder) {
+fn ask_add_field_json(
+    field_name: &str,
+    schema_builder: &mut SchemaBuilder,
+    tokenizers: &mut IndexTokenizers,
+) {
     let mut json_options = JsonObjectOptions::default();
     if prompt_yn("Should the field be stored") {
         let stored: JsonObjectOptions = STORED.into();
@@ -196,7 +307,7 @@ fn ask_add_field_json(field_name: &str, schema_builder: &mut SchemaBuilder) {
 
     if prompt_yn("Should the field be indexed") {
         let with_positions = prompt_yn("Should the indexed json keep positions");
-        let tokenizer = prompt_tokenizer_or_default();
+        let tokenizer = prompt_tokenizer_or_default(tokenizers);
         let index_option = if with_positions {
             IndexRecordOption::WithFreqsAndPositions
         } else {
@@ -258,9 +369,21 @@ fn ask_add_field_ip(field_name: &str, schema_builder: &mut SchemaBuilder) {
     schema_builder.add_ip_addr_field(field_name, ip_addr_options);
 }
 
-fn ask_add_field(schema_builder: &mut SchemaBuilder) {
+/// Asks for the definition of a new field, adds it to the schema builder
+/// and returns its name.
+pub fn ask_add_field(
+    schema_builder: &mut SchemaBuilder,
+    tokenizers: &mut IndexTokenizers,
+    existing_field_names: &[String],
+) -> String {
     println!("\n\n");
-    let field_name = prompt_input("New field name ", field_name_validate);
+    let field_name = prompt_input("New field name ", |field_name| {
+        field_name_validate(field_name)?;
+        if existing_field_names.iter().any(|name| name == field_name) {
+            return Err(format!("Field `{}` already exists", field_name));
+        }
+        Ok(())
+    });
 
     // Manually iterate over tantivy::schema::Type and make strings out of them
     // Can introduce a dependency to do it automatically, but this should be easier
@@ -270,7 +393,7 @@ fn ask_add_field(schema_builder: &mut SchemaBuilder) {
     let field_type = prompt_field_type("Choose Field Type", possible_field_types);
     match field_type {
         Type::Str => {
-            ask_add_field_text(&field_name, schema_builder);
+            ask_add_field_text(&field_name, schema_builder, tokenizers);
         }
         Type::U64 | Type::F64 | Type::I64 | Type::Bool => {
             ask_add_num_field_with_options(&field_name, field_type, schema_builder);
@@ -285,12 +408,13 @@ fn ask_add_field(schema_builder: &mut SchemaBuilder) {
             ask_add_field_bytes(&field_name, schema_builder);
         }
         Type::Json => {
-            ask_add_field_json(&field_name, schema_builder);
+            ask_add_field_json(&field_name, schema_builder, tokenizers);
         }
         Type::IpAddr => {
             ask_add_field_ip(&field_name, schema_builder);
         }
     }
+    field_name
 }
 
 fn inferred_type_label(inferred_type: InferredType) -> &'static str {
@@ -310,7 +434,11 @@ fn compare_inferred_fields(left: &InferredField, right: &InferredField) -> Order
     left.name.cmp(&right.name)
 }
 
-fn add_inferred_text_field(field_name: &str, schema_builder: &mut SchemaBuilder) {
+fn add_inferred_text_field(
+    field_name: &str,
+    schema_builder: &mut SchemaBuilder,
+    tokenizers: &mut IndexTokenizers,
+) {
     let mut text_options = TextOptions::default().set_stored();
     if prompt_yn("Should the field be fast") {
         text_options = text_options.set_fast(None);
@@ -318,7 +446,7 @@ fn add_inferred_text_field(field_name: &str, schema_builder: &mut SchemaBuilder)
 
     if prompt_yn("Should the field be indexed") {
         let with_positions = prompt_yn("Should the indexed text keep positions");
-        let tokenizer = prompt_tokenizer_or_default();
+        let tokenizer = prompt_tokenizer_or_default(tokenizers);
         let index_option = if with_positions {
             IndexRecordOption::WithFreqsAndPositions
         } else {
@@ -385,14 +513,18 @@ fn add_inferred_ip_field(field_name: &str, schema_builder: &mut SchemaBuilder) {
     schema_builder.add_ip_addr_field(field_name, options);
 }
 
-fn add_inferred_json_field(field_name: &str, schema_builder: &mut SchemaBuilder) {
+fn add_inferred_json_field(
+    field_name: &str,
+    schema_builder: &mut SchemaBuilder,
+    tokenizers: &mut IndexTokenizers,
+) {
     let mut json_options: JsonObjectOptions = STORED.into();
     if prompt_yn("Should the field be fast") {
         json_options = json_options.set_fast(None);
     }
     if prompt_yn("Should the field be indexed") {
         let with_positions = prompt_yn("Should the indexed json keep positions");
-        let tokenizer = prompt_tokenizer_or_default();
+        let tokenizer = prompt_tokenizer_or_default(tokenizers);
         let index_option = if with_positions {
             IndexRecordOption::WithFreqsAndPositions
         } else {
@@ -406,7 +538,11 @@ fn add_inferred_json_field(field_name: &str, schema_builder: &mut SchemaBuilder)
     schema_builder.add_json_field(field_name, json_options);
 }
 
-fn ask_add_inferred_field(field: &InferredField, schema_builder: &mut SchemaBuilder) {
+fn ask_add_inferred_field(
+    field: &InferredField,
+    schema_builder: &mut SchemaBuilder,
+    tokenizers: &mut IndexTokenizers,
+) {
     println!(
         "\n{}",
         Style::new().bold().fg(Green).paint(format!(
@@ -416,19 +552,23 @@ fn ask_add_inferred_field(field: &InferredField, schema_builder: &mut SchemaBuil
         ))
     );
     match field.field_type {
-        InferredType::Text => add_inferred_text_field(&field.name, schema_builder),
+        InferredType::Text => add_inferred_text_field(&field.name, schema_builder, tokenizers),
         InferredType::Bool | InferredType::U64 | InferredType::I64 | InferredType::F64 => {
             add_inferred_numeric_field(&field.name, field.field_type, schema_builder)
         }
         InferredType::Date => add_inferred_date_field(&field.name, schema_builder),
         InferredType::IpAddr => add_inferred_ip_field(&field.name, schema_builder),
-        InferredType::Json => add_inferred_json_field(&field.name, schema_builder),
+        InferredType::Json => add_inferred_json_field(&field.name, schema_builder, tokenizers),
     }
 }
 
-fn create_index_with_schema(directory: PathBuf, schema: Schema) -> tantivy::Result<()> {
-    let schema_json = serde_json::to_string_pretty(&schema).unwrap().to_string();
-    println!("\n{}\n", Style::new().fg(Green).paint(schema_json));
+fn create_index_with_schema(
+    directory: PathBuf,
+    schema: Schema,
+    tokenizers: &IndexTokenizers,
+) -> tantivy::Result<()> {
+    validate_schema_tokenizers(&schema, &tokenizers.manager)?;
+    println!("\n{}\n", Style::new().fg(Green).paint(schema_json(&schema)));
     match fs::create_dir(&directory) {
         Ok(_) => (),
         // Proceed here; actual existence of index is checked in Index::create_in_dir
@@ -436,6 +576,9 @@ fn create_index_with_schema(directory: PathBuf, schema: Schema) -> tantivy::Resu
         Err(e) => panic!("{:?}", e),
     };
     Index::create_in_dir(&directory, schema)?;
+    if !tokenizers.configs.is_empty() {
+        save_tokenizer_configs(&directory, &tokenizers.configs)?;
+    }
     Ok(())
 }
 
@@ -443,6 +586,7 @@ fn run_new_with_inferred_schema(
     directory: PathBuf,
     ndjson_path: PathBuf,
     sample_size: usize,
+    tokenizers: &mut IndexTokenizers,
 ) -> Result<(), String> {
     println!(
         "\n{} ",
@@ -478,13 +622,16 @@ fn run_new_with_inferred_schema(
 
     let mut schema_builder = SchemaBuilder::default();
     for field in &inferred_fields {
-        ask_add_inferred_field(field, &mut schema_builder);
+        ask_add_inferred_field(field, &mut schema_builder, tokenizers);
     }
     let schema = schema_builder.build();
-    create_index_with_schema(directory, schema).map_err(|e| format!("{:?}", e))
+    create_index_with_schema(directory, schema, tokenizers).map_err(|e| format!("{:?}", e))
 }
 
-fn run_new_interactive(directory: PathBuf) -> tantivy::Result<()> {
+fn run_new_interactive(
+    directory: PathBuf,
+    tokenizers: &mut IndexTokenizers,
+) -> tantivy::Result<()> {
     println!(
         "\n{} ",
         Style::new().bold().fg(Green).paint("Creating new index")
@@ -497,12 +644,14 @@ fn run_new_interactive(directory: PathBuf) -> tantivy::Result<()> {
             .paint("First define its schema!")
     );
     let mut schema_builder = SchemaBuilder::default();
+    let mut field_names = Vec::new();
     loop {
-        ask_add_field(&mut schema_builder);
+        let field_name = ask_add_field(&mut schema_builder, tokenizers, &field_names);
+        field_names.push(field_name);
         if !prompt_yn("Add another field") {
             break;
         }
     }
     let schema = schema_builder.build();
-    create_index_with_schema(directory, schema)
+    create_index_with_schema(directory, schema, tokenizers)
 }
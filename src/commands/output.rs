@@ -0,0 +1,222 @@
+use serde_json::{Map, Value};
+use std::env;
+use std::io;
+use std::io::Write;
+use tantivy::schema::NamedFieldDocument;
+use terminal_size::{terminal_size, Width};
+
+/// Converts a named document into a JSON object, as `index` expects it.
+///
+/// Tantivy returns every field as an array of values. Single-valued fields
+/// are flattened to a scalar.
+pub fn flatten_named_doc(named_doc: NamedFieldDocument) -> Map<String, Value> {
+    named_doc
+        .0
+        .into_iter()
+        .map(|(field_name, mut values)| {
+            let value = if values.len() == 1 {
+                serde_json::to_value(values.pop().unwrap())
+            } else {
+                serde_json::to_value(values)
+            };
+            (field_name, value.unwrap())
+        })
+        .collect()
+}
+
+/// Text of a CSV cell. Strings are written as is, anything else as JSON.
+pub fn csv_cell(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(text)) => text.clone(),
+        Some(value) => value.to_string(),
+    }
+}
+
+/// Writes a CSV record, quoting the cells when needed.
+pub fn write_csv_record<W: Write>(wrt: &mut W, cells: &[String]) -> io::Result<()> {
+    for (i, cell) in cells.iter().enumerate() {
+        if i > 0 {
+            write!(wrt, ",")?;
+        }
+        if cell.contains([',', '"', '\n', '\r']) {
+            write!(wrt, "\"{}\"", cell.replace('"', "\"\""))?;
+        } else {
+            write!(wrt, "{}", cell)?;
+        }
+    }
+    writeln!(wrt)
+}
+
+/// Separator between the columns of a table.
+const COLUMN_SEPARATOR: &str = "  ";
+
+/// Narrowest a column gets when a table is fitted to the terminal.
+const MIN_COLUMN_WIDTH: usize = 8;
+
+/// Writes rows as a text table, padding each column to its widest cell.
+pub fn write_table<W: Write>(
+    wrt: &mut W,
+    header: &[String],
+    rows: &[Vec<String>],
+) -> io::Result<()> {
+    let widths = column_widths(header, rows);
+    write_table_with_widths(wrt, header, rows, &widths)
+}
+
+/// Writes rows as a text table no wider than `max_width`, if possible,
+/// truncating the cells of the widest columns.
+pub fn write_fitted_table<W: Write>(
+    wrt: &mut W,
+    header: &[String],
+    rows: &[Vec<String>],
+    max_width: usize,
+) -> io::Result<()> {
+    let mut widths = column_widths(header, rows);
+    fit_column_widths(&mut widths, max_width);
+    write_table_with_widths(wrt, header, rows, &widths)
+}
+
+/// Width of the terminal stdout is written to. When stdout is not a
+/// terminal, the `COLUMNS` environment variable, or 120 columns.
+pub fn terminal_width() -> usize {
+    if let Some((Width(width), _)) = terminal_size() {
+        return usize::from(width);
+    }
+    env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse().ok())
+        .unwrap_or(120)
+}
+
+fn column_widths(header: &[String], rows: &[Vec<String>]) -> Vec<usize> {
+    let mut widths: Vec<usize> = header.iter().map(|cell| cell.chars().count()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    widths
+}
+
+/// Shrinks the widest columns until the table fits in `max_width`, without
+/// making any column narrower than `MIN_COLUMN_WIDTH`.
+fn fit_column_widths(widths: &mut [usize], max_width: usize) {
+    let separators_width = COLUMN_SEPARATOR.len() * widths.len().saturating_sub(1);
+    loop {
+        let table_width: usize = widths.iter().sum::<usize>() + separators_width;
+        if table_width <= max_width {
+            return;
+        }
+        let Some(widest) = widths.iter_mut().max() else {
+            return;
+        };
+        if *widest <= MIN_COLUMN_WIDTH {
+            return;
+        }
+        *widest = (*widest - (table_width - max_width)).max(MIN_COLUMN_WIDTH);
+    }
+}
+
+/// Cuts a cell to `width` characters, ending it with `…` if it was cut.
+fn truncate_cell(cell: &str, width: usize) -> String {
+    let cell = cell.replace(['\n', '\r', '\t'], " ");
+    if cell.chars().count() <= width {
+        return cell;
+    }
+    let mut truncated: String = cell.chars().take(width.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
+fn write_table_with_widths<W: Write>(
+    wrt: &mut W,
+    header: &[String],
+    rows: &[Vec<String>],
+    widths: &[usize],
+) -> io::Result<()> {
+    let separator: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+    write_table_row(wrt, header, widths)?;
+    write_table_row(wrt, &separator, widths)?;
+    for row in rows {
+        write_table_row(wrt, row, widths)?;
+    }
+    Ok(())
+}
+
+fn write_table_row<W: Write>(wrt: &mut W, row: &[String], widths: &[usize]) -> io::Result<()> {
+    let cells: Vec<String> = row
+        .iter()
+        .zip(widths)
+        .map(|(cell, width)| format!("{:<width$}", truncate_cell(cell, *width), width = width))
+        .collect();
+    writeln!(wrt, "{}", cells.join(COLUMN_SEPARATOR).trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use tantivy::schema::OwnedValue;
+
+    #[test]
+    fn flatten_single_valued_fields() {
+        let mut fields = BTreeMap::new();
+        fields.insert("title".to_string(), vec![OwnedValue::from("Paris")]);
+        fields.insert(
+            "tags".to_string(),
+            vec![OwnedValue::from("city"), OwnedValue::from("capital")],
+        );
+        let doc = flatten_named_doc(NamedFieldDocument(fields));
+        assert_eq!(
+            Value::Object(doc),
+            serde_json::json!({"title": "Paris", "tags": ["city", "capital"]})
+        );
+    }
+
+    #[test]
+    fn table_columns_are_aligned() {
+        let mut buffer = Vec::new();
+        let header = vec!["name".to_string(), "type".to_string()];
+        let rows = vec![
+            vec!["title".to_string(), "text".to_string()],
+            vec!["id".to_string(), "u64".to_string()],
+        ];
+        write_table(&mut buffer, &header, &rows).unwrap();
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            "name   type\n-----  ----\ntitle  text\nid     u64\n"
+        );
+    }
+
+    #[test]
+    fn fitted_table_truncates_the_widest_column() {
+        let mut buffer = Vec::new();
+        let header = vec!["id".to_string(), "body".to_string()];
+        let rows = vec![vec![
+            "1".to_string(),
+            "Paris is the capital of France".to_string(),
+        ]];
+        write_fitted_table(&mut buffer, &header, &rows, 16).unwrap();
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            "id  body\n--  ------------\n1   Paris is th…\n"
+        );
+    }
+
+    #[test]
+    fn csv_cells_are_quoted_when_needed() {
+        let mut buffer = Vec::new();
+        let cells = vec![
+            csv_cell(Some(&Value::from("Paris, France"))),
+            csv_cell(Some(&Value::from("say \"hi\""))),
+            csv_cell(Some(&Value::from(3))),
+            csv_cell(None),
+        ];
+        write_csv_record(&mut buffer, &cells).unwrap();
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            "\"Paris, France\",\"say \"\"hi\"\"\",3,\n"
+        );
+    }
+}
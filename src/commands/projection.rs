@@ -0,0 +1,202 @@
+use std::collections::BTreeMap;
+use tantivy::columnar::DynamicColumn;
+use tantivy::schema::{Field, FieldType, NamedFieldDocument, OwnedValue, Schema, Value};
+use tantivy::{DocAddress, Document, Searcher, TantivyDocument, TantivyError};
+
+/// The fields returned for each hit.
+pub struct FieldProjection {
+    /// Stored fields read from the doc store, or all of them if `None`.
+    stored_fields: Option<Vec<Field>>,
+    /// Fields which are fast but not stored, read from their fast field
+    /// columns.
+    fast_fields: Vec<Field>,
+}
+
+impl FieldProjection {
+    /// Returns all the stored fields.
+    pub fn all_stored() -> FieldProjection {
+        FieldProjection {
+            stored_fields: None,
+            fast_fields: Vec::new(),
+        }
+    }
+
+    /// Only returns the given fields. Fields that are not stored are read from
+    /// their fast field, if they have one.
+    pub fn new(schema: &Schema, field_names: &[String]) -> tantivy::Result<FieldProjection> {
+        let mut stored_fields = Vec::new();
+        let mut fast_fields = Vec::new();
+        for field_name in field_names {
+            let field = schema.get_field(field_name)?;
+            let field_entry = schema.get_field_entry(field);
+            if field_entry.is_stored() {
+                stored_fields.push(field);
+            } else if field_entry.is_fast()
+                && !matches!(field_entry.field_type(), FieldType::JsonObject(_))
+            {
+                fast_fields.push(field);
+            } else {
+                return Err(TantivyError::InvalidArgument(format!(
+                    "Field `{}` is neither stored nor fast, its values cannot be returned",
+                    field_name
+                )));
+            }
+        }
+        Ok(FieldProjection {
+            stored_fields: Some(stored_fields),
+            fast_fields,
+        })
+    }
+
+    /// Whether the document has to be read from the doc store.
+    pub fn needs_stored_doc(&self) -> bool {
+        self.stored_fields
+            .as_ref()
+            .is_none_or(|stored_fields| !stored_fields.is_empty())
+    }
+
+    /// Returns the projected fields of a document. `doc` is the stored
+    /// document, which must be given if `needs_stored_doc` is true.
+    pub fn named_doc(
+        &self,
+        searcher: &Searcher,
+        doc_address: DocAddress,
+        doc: Option<&TantivyDocument>,
+    ) -> tantivy::Result<NamedFieldDocument> {
+        let schema = searcher.schema();
+        let mut field_map: BTreeMap<String, Vec<OwnedValue>> = BTreeMap::new();
+        if let Some(doc) = doc {
+            for (field, value) in doc.iter_fields_and_values() {
+                let is_projected = self
+                    .stored_fields
+                    .as_ref()
+                    .is_none_or(|stored_fields| stored_fields.contains(&field));
+                if is_projected {
+                    field_map
+                        .entry(schema.get_field_name(field).to_string())
+                        .or_default()
+                        .push(OwnedValue::from(value.as_value()));
+                }
+            }
+        }
+        let segment_reader = searcher.segment_reader(doc_address.segment_ord);
+        for &field in &self.fast_fields {
+            let field_name = schema.get_field_name(field);
+            let mut values = Vec::new();
+            for column_handle in segment_reader
+                .fast_fields()
+                .dynamic_column_handles(field_name)?
+            {
+                push_fast_values(&column_handle.open()?, doc_address.doc_id, &mut values)?;
+            }
+            if !values.is_empty() {
+                field_map.insert(field_name.to_string(), values);
+            }
+        }
+        Ok(NamedFieldDocument(field_map))
+    }
+}
+
+fn push_fast_values(
+    column: &DynamicColumn,
+    doc_id: u32,
+    values: &mut Vec<OwnedValue>,
+) -> tantivy::Result<()> {
+    match column {
+        DynamicColumn::Bool(column) => {
+            values.extend(column.values_for_doc(doc_id).map(OwnedValue::Bool))
+        }
+        DynamicColumn::I64(column) => {
+            values.extend(column.values_for_doc(doc_id).map(OwnedValue::I64))
+        }
+        DynamicColumn::U64(column) => {
+            values.extend(column.values_for_doc(doc_id).map(OwnedValue::U64))
+        }
+        DynamicColumn::F64(column) => {
+            values.extend(column.values_for_doc(doc_id).map(OwnedValue::F64))
+        }
+        DynamicColumn::IpAddr(column) => {
+            values.extend(column.values_for_doc(doc_id).map(OwnedValue::IpAddr))
+        }
+        DynamicColumn::DateTime(column) => {
+            values.extend(column.values_for_doc(doc_id).map(OwnedValue::Date))
+        }
+        DynamicColumn::Bytes(column) => {
+            for term_ord in column.term_ords(doc_id) {
+                let mut bytes = Vec::new();
+                column.ord_to_bytes(term_ord, &mut bytes)?;
+                values.push(OwnedValue::Bytes(bytes));
+            }
+        }
+        DynamicColumn::Str(column) => {
+            for term_ord in column.term_ords(doc_id) {
+                let mut text = String::new();
+                column.ord_to_str(term_ord, &mut text)?;
+                values.push(OwnedValue::Str(text));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_index::create_index;
+    use tantivy::doc;
+    use tantivy::schema::{FAST, INDEXED, STORED, STRING, TEXT};
+
+    fn test_schema() -> Schema {
+        let mut schema_builder = Schema::builder();
+        schema_builder.add_text_field("title", TEXT | STORED);
+        schema_builder.add_text_field("body", TEXT | STORED);
+        schema_builder.add_u64_field("views", FAST);
+        schema_builder.add_text_field("tag", STRING | FAST);
+        schema_builder.add_u64_field("rank", INDEXED);
+        schema_builder.build()
+    }
+
+    #[test]
+    fn projection_reads_stored_and_fast_fields() {
+        let schema = test_schema();
+        let field = |name| schema.get_field(name).unwrap();
+        let doc = doc!(
+            field("title") => "Paris",
+            field("body") => "Capital of France",
+            field("views") => 3u64,
+            field("tag") => "city"
+        );
+        let searcher = create_index(schema.clone(), [doc])
+            .reader()
+            .unwrap()
+            .searcher();
+        let doc_address = DocAddress::new(0, 0);
+        let doc: TantivyDocument = searcher.doc(doc_address).unwrap();
+
+        let field_names = vec!["title".to_string(), "views".to_string(), "tag".to_string()];
+        let projection = FieldProjection::new(&schema, &field_names).unwrap();
+        assert!(projection.needs_stored_doc());
+        let named_doc = projection
+            .named_doc(&searcher, doc_address, Some(&doc))
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(&named_doc).unwrap(),
+            serde_json::json!({"title": ["Paris"], "views": [3], "tag": ["city"]})
+        );
+
+        let projection = FieldProjection::new(&schema, &["views".to_string()]).unwrap();
+        assert!(!projection.needs_stored_doc());
+        let named_doc = projection.named_doc(&searcher, doc_address, None).unwrap();
+        assert_eq!(
+            serde_json::to_value(&named_doc).unwrap(),
+            serde_json::json!({"views": [3]})
+        );
+    }
+
+    #[test]
+    fn fields_neither_stored_nor_fast_cannot_be_projected() {
+        let schema = test_schema();
+        assert!(FieldProjection::new(&schema, &["rank".to_string()]).is_err());
+        assert!(FieldProjection::new(&schema, &["missing".to_string()]).is_err());
+    }
+}
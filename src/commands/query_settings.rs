@@ -0,0 +1,333 @@
+use clap::ArgMatches;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use tantivy::query::QueryParser;
+use tantivy::schema::{Field, FieldType};
+use tantivy::{Index, Score, TantivyError};
+
+use crate::commands::search::default_search_fields;
+
+/// Name of the file, stored alongside `meta.json`, holding the query parser
+/// settings of an index.
+pub const QUERY_SETTINGS_FILENAME: &str = "query_settings.json";
+
+/// How queries are parsed by `search`, `serve` and `bench`.
+///
+/// For instance
+///
+/// ```json
+/// {
+///   "default_fields": ["title", "body"],
+///   "boosts": {"title": 2.0},
+///   "conjunction_by_default": true,
+///   "fuzzy": {"title": {"distance": 1, "prefix": false, "transposition_cost_one": true}}
+/// }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct QuerySettings {
+    /// Fields searched when a query does not target a specific field. All
+    /// the indexed text fields if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_fields: Option<Vec<String>>,
+    /// Boost applied to the terms of each field.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub boosts: BTreeMap<String, Score>,
+    /// Whether all the terms of a query must match, rather than any of them.
+    /// Any of them if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conjunction_by_default: Option<bool>,
+    /// Fields whose terms also match terms within an edit distance.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fuzzy: BTreeMap<String, FuzzySettings>,
+}
+
+/// Fuzzy matching of the terms of a field.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FuzzySettings {
+    /// Maximum Levenshtein distance, at most 2.
+    pub distance: u8,
+    /// Whether the terms also match as prefixes.
+    #[serde(default)]
+    pub prefix: bool,
+    /// Whether swapping two adjacent characters counts as a single edit.
+    #[serde(default = "default_transposition_cost_one")]
+    pub transposition_cost_one: bool,
+}
+
+fn default_transposition_cost_one() -> bool {
+    true
+}
+
+/// Largest edit distance supported by tantivy's fuzzy queries.
+const MAX_FUZZY_DISTANCE: u8 = 2;
+
+impl QuerySettings {
+    /// Reads the `--default-fields`, `--boost`, `--conjunction`,
+    /// `--no-conjunction` and `--fuzzy` options.
+    pub fn from_matches(matches: &ArgMatches) -> Result<QuerySettings, String> {
+        let default_fields = matches.get_one::<String>("default_fields").map(|fields| {
+            fields
+                .split(',')
+                .map(|field| field.trim().to_string())
+                .filter(|field| !field.is_empty())
+                .collect()
+        });
+        let boosts = matches
+            .get_many::<String>("boost")
+            .into_iter()
+            .flatten()
+            .map(|boost| parse_boost(boost))
+            .collect::<Result<_, _>>()?;
+        let fuzzy = matches
+            .get_many::<String>("fuzzy")
+            .into_iter()
+            .flatten()
+            .map(|fuzzy| parse_fuzzy(fuzzy))
+            .collect::<Result<_, _>>()?;
+        let conjunction_by_default = if matches.get_flag("conjunction") {
+            Some(true)
+        } else if matches.get_flag("no_conjunction") {
+            Some(false)
+        } else {
+            None
+        };
+        Ok(QuerySettings {
+            default_fields,
+            boosts,
+            conjunction_by_default,
+            fuzzy,
+        })
+    }
+
+    /// Returns these settings, overridden by the ones set in `overrides`.
+    pub fn overridden_by(mut self, overrides: QuerySettings) -> QuerySettings {
+        if overrides.default_fields.is_some() {
+            self.default_fields = overrides.default_fields;
+        }
+        self.boosts.extend(overrides.boosts);
+        if overrides.conjunction_by_default.is_some() {
+            self.conjunction_by_default = overrides.conjunction_by_default;
+        }
+        self.fuzzy.extend(overrides.fuzzy);
+        self
+    }
+
+    /// Builds the query parser of an index with these settings.
+    pub fn query_parser(&self, index: &Index) -> tantivy::Result<QueryParser> {
+        let schema = index.schema();
+        let default_fields: Vec<Field> = match &self.default_fields {
+            Some(field_names) => field_names
+                .iter()
+                .map(|field_name| {
+                    let field = schema.get_field(field_name)?;
+                    if !schema.get_field_entry(field).is_indexed() {
+                        return Err(TantivyError::InvalidArgument(format!(
+                            "Field `{}` is not indexed and cannot be searched by default",
+                            field_name
+                        )));
+                    }
+                    Ok(field)
+                })
+                .collect::<tantivy::Result<_>>()?,
+            None => default_search_fields(&schema),
+        };
+        let mut query_parser =
+            QueryParser::new(schema.clone(), default_fields, index.tokenizers().clone());
+        for (field_name, boost) in &self.boosts {
+            query_parser.set_field_boost(schema.get_field(field_name)?, *boost);
+        }
+        if self.conjunction_by_default == Some(true) {
+            query_parser.set_conjunction_by_default();
+        }
+        for (field_name, fuzzy) in &self.fuzzy {
+            let field = schema.get_field(field_name)?;
+            if !matches!(
+                schema.get_field_entry(field).field_type(),
+                FieldType::Str(_)
+            ) {
+                return Err(TantivyError::InvalidArgument(format!(
+                    "Field `{}` is not a text field, fuzzy matching only applies to text",
+                    field_name
+                )));
+            }
+            if fuzzy.distance > MAX_FUZZY_DISTANCE {
+                return Err(TantivyError::InvalidArgument(format!(
+                    "The fuzzy distance of field `{}` must be at most {}",
+                    field_name, MAX_FUZZY_DISTANCE
+                )));
+            }
+            query_parser.set_field_fuzzy(
+                field,
+                fuzzy.prefix,
+                fuzzy.distance,
+                fuzzy.transposition_cost_one,
+            );
+        }
+        Ok(query_parser)
+    }
+}
+
+/// Parses a `field=boost` option.
+fn parse_boost(boost: &str) -> Result<(String, Score), String> {
+    let invalid_boost = || format!("Invalid boost `{}`, expected `field=boost`", boost);
+    let (field_name, value) = boost.split_once('=').ok_or_else(invalid_boost)?;
+    let value: Score = value.trim().parse().map_err(|_| invalid_boost())?;
+    if field_name.trim().is_empty() || !value.is_finite() || value < 0.0 {
+        return Err(invalid_boost());
+    }
+    Ok((field_name.trim().to_string(), value))
+}
+
+/// Parses a `field[:distance][:prefix][:no-transposition]` option. The
+/// distance defaults to 1.
+pub fn parse_fuzzy(fuzzy: &str) -> Result<(String, FuzzySettings), String> {
+    let invalid_fuzzy = || {
+        format!(
+            "Invalid fuzzy option `{}`, expected `field[:distance][:prefix][:no-transposition]`",
+            fuzzy
+        )
+    };
+    let mut parts = fuzzy.split(':').map(str::trim);
+    let field_name = parts.next().unwrap_or_default();
+    if field_name.is_empty() {
+        return Err(invalid_fuzzy());
+    }
+    let mut settings = FuzzySettings {
+        distance: 1,
+        prefix: false,
+        transposition_cost_one: true,
+    };
+    for part in parts {
+        match part {
+            "prefix" => settings.prefix = true,
+            "no-transposition" => settings.transposition_cost_one = false,
+            distance => {
+                settings.distance = distance.parse().map_err(|_| invalid_fuzzy())?;
+                if settings.distance > MAX_FUZZY_DISTANCE {
+                    return Err(format!(
+                        "Invalid fuzzy option `{}`, the distance must be at most {}",
+                        fuzzy, MAX_FUZZY_DISTANCE
+                    ));
+                }
+            }
+        }
+    }
+    Ok((field_name.to_string(), settings))
+}
+
+/// Reads the query settings stored in an index directory.
+///
+/// An index without a query settings file uses the default settings.
+pub fn load_query_settings(directory: &Path) -> tantivy::Result<QuerySettings> {
+    match fs::read_to_string(directory.join(QUERY_SETTINGS_FILENAME)) {
+        Ok(json) => serde_json::from_str(&json).map_err(|e| {
+            TantivyError::InvalidArgument(format!("Invalid {}: {}", QUERY_SETTINGS_FILENAME, e))
+        }),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(QuerySettings::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub fn save_query_settings(directory: &Path, settings: &QuerySettings) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(settings)?;
+    fs::write(directory.join(QUERY_SETTINGS_FILENAME), json)
+}
+
+/// Builds the query parser of the index stored in `directory`, with its
+/// stored settings overridden by the command line options.
+pub fn index_query_parser(
+    index: &Index,
+    directory: &Path,
+    overrides: QuerySettings,
+) -> tantivy::Result<QueryParser> {
+    load_query_settings(directory)?
+        .overridden_by(overrides)
+        .query_parser(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_boosts() {
+        assert_eq!(parse_boost("title=2.5"), Ok(("title".to_string(), 2.5)));
+        assert!(parse_boost("title").is_err());
+        assert!(parse_boost("title=high").is_err());
+        assert!(parse_boost("=2").is_err());
+        assert!(parse_boost("title=-1").is_err());
+    }
+
+    #[test]
+    fn command_line_overrides_index_settings() {
+        let index_settings: QuerySettings =
+            serde_json::from_str(r#"{"default_fields": ["title"], "boosts": {"title": 2.0}}"#)
+                .unwrap();
+        let mut boosts = BTreeMap::new();
+        boosts.insert("body".to_string(), 0.5);
+        let settings = index_settings.clone().overridden_by(QuerySettings {
+            boosts,
+            ..QuerySettings::default()
+        });
+        assert_eq!(settings.default_fields, Some(vec!["title".to_string()]));
+        assert_eq!(settings.boosts.len(), 2);
+        let settings = index_settings.overridden_by(QuerySettings {
+            default_fields: Some(vec!["body".to_string()]),
+            ..QuerySettings::default()
+        });
+        assert_eq!(settings.default_fields, Some(vec!["body".to_string()]));
+        assert_eq!(settings.boosts["title"], 2.0);
+    }
+
+    #[test]
+    fn command_line_overrides_conjunction_both_ways() {
+        let conjunction = |conjunction_by_default| QuerySettings {
+            conjunction_by_default,
+            ..QuerySettings::default()
+        };
+        assert_eq!(
+            conjunction(Some(true)).overridden_by(conjunction(Some(false))),
+            conjunction(Some(false))
+        );
+        assert_eq!(
+            conjunction(Some(false)).overridden_by(conjunction(Some(true))),
+            conjunction(Some(true))
+        );
+        assert_eq!(
+            conjunction(Some(true)).overridden_by(conjunction(None)),
+            conjunction(Some(true))
+        );
+    }
+
+    #[test]
+    fn parse_fuzzy_options() {
+        assert_eq!(
+            parse_fuzzy("title"),
+            Ok((
+                "title".to_string(),
+                FuzzySettings {
+                    distance: 1,
+                    prefix: false,
+                    transposition_cost_one: true
+                }
+            ))
+        );
+        assert_eq!(
+            parse_fuzzy("title:2:prefix:no-transposition"),
+            Ok((
+                "title".to_string(),
+                FuzzySettings {
+                    distance: 2,
+                    prefix: true,
+                    transposition_cost_one: false
+                }
+            ))
+        );
+        assert!(parse_fuzzy("title:3").is_err());
+        assert!(parse_fuzzy("title:fast").is_err());
+        assert!(parse_fuzzy(":1").is_err());
+    }
+}
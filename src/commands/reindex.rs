@@ -0,0 +1,235 @@
+use clap::ArgMatches;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::thread;
+use tantivy::schema::{NamedFieldDocument, Schema};
+use tantivy::{Document, TantivyDocument, TantivyError};
+
+use crate::commands::export::for_each_stored_document;
+use crate::commands::index::index_json_lines;
+use crate::commands::query_settings::load_query_settings;
+use crate::commands::tokenizers::open_index;
+
+pub fn run_reindex_cli(argmatch: &ArgMatches) -> Result<(), String> {
+    let from_directory = PathBuf::from(argmatch.get_one::<String>("from").unwrap());
+    let to_directory = PathBuf::from(argmatch.get_one::<String>("to").unwrap());
+    let mapping_file = argmatch.get_one::<String>("mapping").map(PathBuf::from);
+    let query = argmatch.get_one::<String>("query").cloned();
+    let mut num_threads: usize = *ArgMatches::get_one(argmatch, "num_threads")
+        .expect("Failed to read num_threads argument as an integer.");
+    if num_threads == 0 {
+        num_threads = 1;
+    }
+    let buffer_size: usize = *ArgMatches::get_one(argmatch, "memory_size")
+        .expect("Failed to read the buffer size argument as an integer.");
+    let buffer_size_per_thread = buffer_size / num_threads;
+    run_reindex(
+        &from_directory,
+        &to_directory,
+        mapping_file.as_deref(),
+        query,
+        buffer_size_per_thread,
+        num_threads,
+    )
+    .map_err(|e| format!("Reindexing failed : {:?}", e))
+}
+
+/// Why the values of a source field will not make it to the target index.
+#[derive(Debug, PartialEq)]
+enum DropReason {
+    NotStored,
+    NotInTarget,
+}
+
+/// Source field name to target field name, for every field that is copied.
+type FieldMapping = BTreeMap<String, String>;
+
+/// Computes which source field goes to which target field.
+///
+/// `renames` maps source field names to target field names. Fields that are
+/// not renamed are copied to the target field with the same name, if any.
+fn plan_field_mapping(
+    source_schema: &Schema,
+    target_schema: &Schema,
+    renames: &BTreeMap<String, String>,
+) -> tantivy::Result<(FieldMapping, Vec<(String, DropReason)>)> {
+    for (source_name, target_name) in renames {
+        source_schema.get_field(source_name)?;
+        target_schema.get_field(target_name)?;
+    }
+    let mut mapping = FieldMapping::new();
+    let mut dropped_fields = Vec::new();
+    for (_, field_entry) in source_schema.fields() {
+        let source_name = field_entry.name();
+        let target_name = renames
+            .get(source_name)
+            .map(String::as_str)
+            .unwrap_or(source_name);
+        if !field_entry.is_stored() {
+            dropped_fields.push((source_name.to_string(), DropReason::NotStored));
+        } else if target_schema.get_field(target_name).is_err() {
+            dropped_fields.push((source_name.to_string(), DropReason::NotInTarget));
+        } else {
+            mapping.insert(source_name.to_string(), target_name.to_string());
+        }
+    }
+    Ok((mapping, dropped_fields))
+}
+
+fn read_renames(mapping_file: &Path) -> tantivy::Result<BTreeMap<String, String>> {
+    let mapping_json = fs::read_to_string(mapping_file)?;
+    serde_json::from_str(&mapping_json).map_err(|e| {
+        TantivyError::InvalidArgument(format!(
+            "The mapping file should be a JSON object of source to target field names: {}",
+            e
+        ))
+    })
+}
+
+fn remap_document(named_doc: NamedFieldDocument, mapping: &FieldMapping) -> NamedFieldDocument {
+    let mut remapped = BTreeMap::new();
+    for (source_name, values) in named_doc.0 {
+        if let Some(target_name) = mapping.get(&source_name) {
+            remapped
+                .entry(target_name.clone())
+                .or_insert_with(Vec::new)
+                .extend(values);
+        }
+    }
+    NamedFieldDocument(remapped)
+}
+
+fn run_reindex(
+    from_directory: &Path,
+    to_directory: &Path,
+    mapping_file: Option<&Path>,
+    query: Option<String>,
+    buffer_size_per_thread: usize,
+    num_threads: usize,
+) -> tantivy::Result<()> {
+    if from_directory.canonicalize()? == to_directory.canonicalize()? {
+        return Err(TantivyError::InvalidArgument(
+            "The source and target indexes must be different".to_string(),
+        ));
+    }
+    let source_index = open_index(from_directory)?;
+    let target_index = open_index(to_directory)?;
+    let source_schema = source_index.schema();
+    let renames = match mapping_file {
+        Some(mapping_file) => read_renames(mapping_file)?,
+        None => BTreeMap::new(),
+    };
+    let (mapping, dropped_fields) =
+        plan_field_mapping(&source_schema, &target_index.schema(), &renames)?;
+    for (field_name, reason) in &dropped_fields {
+        match reason {
+            DropReason::NotStored => println!(
+                "Field `{}` is not stored in the source index, its values are dropped",
+                field_name
+            ),
+            DropReason::NotInTarget => println!(
+                "Field `{}` does not exist in the target index, its values are dropped",
+                field_name
+            ),
+        }
+    }
+    // Validate the query before spawning the reader thread.
+    let query = query
+        .map(|query| {
+            let query_parser = load_query_settings(from_directory)?.query_parser(&source_index)?;
+            query_parser.parse_query(&query).map_err(TantivyError::from)
+        })
+        .transpose()?;
+
+    let (line_sender, line_receiver) = crossbeam_channel::bounded(100);
+    let reader_thread = thread::spawn(move || -> tantivy::Result<u64> {
+        let searcher = source_index.reader()?.searcher();
+        let mut num_docs = 0;
+        for_each_stored_document(&searcher, query.as_deref(), |doc: TantivyDocument| {
+            let named_doc = remap_document(doc.to_named_doc(&source_schema), &mapping);
+            let line = serde_json::to_string(&named_doc)
+                .map_err(|e| TantivyError::InternalError(e.to_string()))?;
+            line_sender.send(line).map_err(|_| {
+                TantivyError::ErrorInThread("The target index stopped indexing".to_string())
+            })?;
+            num_docs += 1;
+            Ok(())
+        })?;
+        Ok(num_docs)
+    });
+
+    let mut num_docs = 0;
+    // The source index must have been read entirely, and all its documents
+    // accepted by the target schema, before the target index is committed, so
+    // that a failure does not leave a partial copy.
+    index_json_lines(
+        &target_index,
+        to_directory,
+        line_receiver,
+        buffer_size_per_thread,
+        num_threads,
+        false,
+        false,
+        |num_rejected_docs| {
+            num_docs = reader_thread.join().map_err(|_| {
+                TantivyError::ErrorInThread("Reading the source index failed".to_string())
+            })??;
+            if num_rejected_docs > 0 {
+                return Err(TantivyError::InvalidArgument(format!(
+                    "{} of the {} documents were rejected by the target schema, none were \
+                     reindexed",
+                    num_rejected_docs, num_docs
+                )));
+            }
+            Ok(())
+        },
+    )?;
+    println!("Reindexed {} documents", num_docs);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tantivy::schema::{FAST, STORED, TEXT};
+
+    #[test]
+    fn plan_field_mapping_renames_and_drops() {
+        let mut source_builder = Schema::builder();
+        source_builder.add_text_field("title", TEXT | STORED);
+        source_builder.add_text_field("body", TEXT);
+        source_builder.add_u64_field("views", FAST | STORED);
+        source_builder.add_text_field("url", STORED);
+        let mut target_builder = Schema::builder();
+        target_builder.add_text_field("name", TEXT | STORED);
+        target_builder.add_text_field("body", TEXT | STORED);
+        target_builder.add_u64_field("views", FAST);
+        let mut renames = BTreeMap::new();
+        renames.insert("title".to_string(), "name".to_string());
+
+        let (mapping, dropped_fields) =
+            plan_field_mapping(&source_builder.build(), &target_builder.build(), &renames).unwrap();
+        assert_eq!(mapping.len(), 2);
+        assert_eq!(mapping["title"], "name");
+        assert_eq!(mapping["views"], "views");
+        assert_eq!(
+            dropped_fields,
+            vec![
+                ("body".to_string(), DropReason::NotStored),
+                ("url".to_string(), DropReason::NotInTarget),
+            ]
+        );
+    }
+
+    #[test]
+    fn plan_field_mapping_rejects_unknown_fields() {
+        let mut schema_builder = Schema::builder();
+        schema_builder.add_text_field("title", TEXT | STORED);
+        let schema = schema_builder.build();
+        let mut renames = BTreeMap::new();
+        renames.insert("title".to_string(), "name".to_string());
+        assert!(plan_field_mapping(&schema, &schema, &renames).is_err());
+    }
+}
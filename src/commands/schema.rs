@@ -0,0 +1,625 @@
+use ansi_term::Colour::Green;
+use ansi_term::Style;
+use clap::ArgMatches;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use tantivy::schema::{FieldEntry, FieldType, IndexRecordOption, Schema, SchemaBuilder};
+use tantivy::{Index, IndexWriter, TantivyDocument, TantivyError};
+
+use super::new::{ask_add_field, prompt_yn, IndexTokenizers};
+use super::output::write_table;
+use super::query_settings::{load_query_settings, save_query_settings, QuerySettings};
+use super::tokenizers::{
+    load_tokenizer_configs, open_index, register_tokenizers, save_tokenizer_configs,
+    validate_schema_tokenizers,
+};
+
+const HEAP_SIZE: usize = 300_000_000;
+
+pub fn run_schema_cli(matches: &ArgMatches) -> Result<(), String> {
+    let (subcommand, options) = matches.subcommand().unwrap();
+    match subcommand {
+        "add-field" => run_add_field_cli(options),
+        "diff" => run_diff_cli(options),
+        "show" => run_show_cli(options),
+        "query-settings" => run_query_settings_cli(options),
+        _ => panic!("Subcommand {} is unknown", subcommand),
+    }
+}
+
+fn run_add_field_cli(matches: &ArgMatches) -> Result<(), String> {
+    let index_directory = PathBuf::from(matches.get_one::<String>("index").unwrap());
+    run_add_field(&index_directory, ask_new_fields).map_err(|e| format!("{:?}", e))
+}
+
+/// Runs the wizard of `new` to add fields to `schema_builder`, which holds
+/// the `field_names` fields of the index.
+fn ask_new_fields(
+    schema_builder: &mut SchemaBuilder,
+    tokenizers: &mut IndexTokenizers,
+    mut field_names: Vec<String>,
+) {
+    loop {
+        let field_name = ask_add_field(schema_builder, tokenizers, &field_names);
+        field_names.push(field_name);
+        if !prompt_yn("Add another field") {
+            break;
+        }
+    }
+}
+
+/// Tantivy schemas are immutable: adding a field means building a new index
+/// with the extended schema, and copying every document from the doc store.
+///
+/// The fields to add are defined by `add_fields`.
+fn run_add_field(
+    directory: &Path,
+    add_fields: impl FnOnce(&mut SchemaBuilder, &mut IndexTokenizers, Vec<String>),
+) -> tantivy::Result<()> {
+    // The directory itself is not canonicalized, as it may be the symbolic
+    // link left by a previous swap, which the next swap replaces.
+    let directory = absolute_directory(directory)?;
+    let index = open_index(&directory)?;
+    let schema = index.schema();
+    check_all_fields_stored(&schema)?;
+
+    println!(
+        "\n{} ",
+        Style::new()
+            .bold()
+            .fg(Green)
+            .paint("Adding fields to the index schema")
+    );
+    let mut tokenizers = IndexTokenizers::new(load_tokenizer_configs(&directory)?)?;
+    let query_settings = load_query_settings(&directory)?;
+    let mut schema_builder = Schema::builder();
+    let mut field_names = Vec::new();
+    for (_, field_entry) in schema.fields() {
+        schema_builder.add_field(field_entry.clone());
+        field_names.push(field_entry.name().to_string());
+    }
+    add_fields(&mut schema_builder, &mut tokenizers, field_names);
+    let new_schema = schema_builder.build();
+    validate_schema_tokenizers(&new_schema, &tokenizers.manager)?;
+    println!(
+        "\n{}\n",
+        Style::new().fg(Green).paint(schema_json(&new_schema))
+    );
+
+    let new_directory = sibling_directory(&directory, "add-field");
+    fs::create_dir(&new_directory)?;
+    let copy_result = (|| {
+        let new_index = Index::builder()
+            .schema(new_schema)
+            .settings(index.settings().clone())
+            .create_in_dir(&new_directory)?;
+        if !tokenizers.configs.is_empty() {
+            save_tokenizer_configs(&new_directory, &tokenizers.configs)?;
+        }
+        register_tokenizers(&new_index, &tokenizers.configs)?;
+        if query_settings != QuerySettings::default() {
+            save_query_settings(&new_directory, &query_settings)?;
+        }
+        let mut index_writer: IndexWriter = new_index.writer(HEAP_SIZE)?;
+        let num_docs = copy_stored_documents(&index, &mut index_writer)?;
+        index_writer.commit()?;
+        index_writer.wait_merging_threads()?;
+        Ok::<_, TantivyError>(num_docs)
+    })();
+    let num_docs = match copy_result {
+        Ok(num_docs) => num_docs,
+        Err(e) => {
+            fs::remove_dir_all(&new_directory)?;
+            return Err(e);
+        }
+    };
+    println!("Copied {} documents", num_docs);
+
+    drop(index);
+    swap_directories(&directory, &new_directory)?;
+    println!("Index {:?} now has the new schema", directory);
+    Ok(())
+}
+
+fn run_show_cli(matches: &ArgMatches) -> Result<(), String> {
+    let index_directory = PathBuf::from(matches.get_one::<String>("index").unwrap());
+    let schema = open_index(&index_directory)
+        .map_err(|e| format!("{:?}", e))?
+        .schema();
+    if matches.get_flag("json") {
+        println!("{}", schema_json(&schema));
+        return Ok(());
+    }
+    let summaries: Vec<FieldSummary> = schema
+        .fields()
+        .map(|(_, field_entry)| FieldSummary::new(field_entry))
+        .collect();
+    let mut header = vec!["name".to_string()];
+    let mut rows: Vec<Vec<String>> = summaries
+        .iter()
+        .map(|summary| vec![summary.name.clone()])
+        .collect();
+    if let Some(first_summary) = summaries.first() {
+        header.extend(
+            first_summary
+                .options()
+                .into_iter()
+                .map(|(option, _)| option.to_string()),
+        );
+    }
+    for (row, summary) in rows.iter_mut().zip(&summaries) {
+        row.extend(summary.options().into_iter().map(|(_, value)| value));
+    }
+    let mut stdout = io::stdout();
+    write_table(&mut stdout, &header, &rows).map_err(|e| format!("{:?}", e))
+}
+
+/// Prints the query settings of an index, after applying the changes given
+/// on the command line, if any.
+fn run_query_settings_cli(matches: &ArgMatches) -> Result<(), String> {
+    let index_directory = PathBuf::from(matches.get_one::<String>("index").unwrap());
+    let changes = QuerySettings::from_matches(matches)?;
+    run_query_settings(&index_directory, changes).map_err(|e| format!("{:?}", e))
+}
+
+fn run_query_settings(directory: &Path, changes: QuerySettings) -> tantivy::Result<()> {
+    let index = open_index(directory)?;
+    let mut query_settings = load_query_settings(directory)?;
+    if changes != QuerySettings::default() {
+        query_settings = query_settings.overridden_by(changes);
+        // Fails on unknown or unindexed fields.
+        query_settings.query_parser(&index)?;
+        save_query_settings(directory, &query_settings)?;
+    }
+    println!("{}", serde_json::to_string_pretty(&query_settings).unwrap());
+    Ok(())
+}
+
+/// The schema as tantivy serializes it in `meta.json`.
+pub fn schema_json(schema: &Schema) -> String {
+    serde_json::to_string_pretty(schema).unwrap()
+}
+
+fn run_diff_cli(matches: &ArgMatches) -> Result<(), String> {
+    let left = PathBuf::from(matches.get_one::<String>("left").unwrap());
+    let right = PathBuf::from(matches.get_one::<String>("right").unwrap());
+    let left_schema = load_schema(&left).map_err(|e| format!("{:?}", e))?;
+    let right_schema = load_schema(&right).map_err(|e| format!("{:?}", e))?;
+    let field_diffs = diff_schemas(&left_schema, &right_schema);
+    let mut num_incompatibilities = 0;
+    for field_diff in &field_diffs {
+        match field_diff {
+            FieldDiff::Added(field) => {
+                println!("+ {} ({})", field.name, field.field_type);
+            }
+            FieldDiff::Removed(field) => {
+                num_incompatibilities += 1;
+                println!("- {} ({})", field.name, field.field_type);
+            }
+            FieldDiff::Changed { name, changes } => {
+                num_incompatibilities += 1;
+                println!("~ {}", name);
+                for (option, left_value, right_value) in changes {
+                    println!("    {}: {} -> {}", option, left_value, right_value);
+                }
+            }
+        }
+    }
+    if field_diffs.is_empty() {
+        println!("Schemas are identical");
+    }
+    if num_incompatibilities > 0 {
+        return Err(format!(
+            "Schemas are incompatible: {} removed or changed fields",
+            num_incompatibilities
+        ));
+    }
+    Ok(())
+}
+
+/// Reads the schema of an index directory, or of a JSON file holding
+/// either a schema or an index `meta.json`.
+fn load_schema(path: &Path) -> tantivy::Result<Schema> {
+    if path.is_dir() {
+        return Ok(open_index(path)?.schema());
+    }
+    let json = fs::read_to_string(path)?;
+    let invalid_schema = |e: serde_json::Error| {
+        TantivyError::InvalidArgument(format!("{:?} is not a valid schema: {}", path, e))
+    };
+    let schema_json = match serde_json::from_str(&json).map_err(invalid_schema)? {
+        serde_json::Value::Object(mut meta) => meta.remove("schema").unwrap_or_default(),
+        schema_json => schema_json,
+    };
+    serde_json::from_value(schema_json).map_err(invalid_schema)
+}
+
+/// Options of a field, as shown by `schema show` and compared by `schema diff`.
+#[derive(Debug, PartialEq)]
+pub struct FieldSummary {
+    pub name: String,
+    pub field_type: &'static str,
+    pub stored: bool,
+    pub indexed: bool,
+    pub fast: bool,
+    pub tokenizer: Option<String>,
+    pub record_option: Option<&'static str>,
+    pub fast_tokenizer: Option<String>,
+}
+
+impl FieldSummary {
+    pub fn new(field_entry: &FieldEntry) -> FieldSummary {
+        let field_type = field_entry.field_type();
+        let (field_type_label, indexing_options, fast_tokenizer) = match field_type {
+            FieldType::Str(options) => (
+                "text",
+                options.get_indexing_options(),
+                options.get_fast_field_tokenizer_name(),
+            ),
+            FieldType::JsonObject(options) => (
+                "json_object",
+                options.get_text_indexing_options(),
+                options.get_fast_field_tokenizer_name(),
+            ),
+            FieldType::U64(_) => ("u64", None, None),
+            FieldType::I64(_) => ("i64", None, None),
+            FieldType::F64(_) => ("f64", None, None),
+            FieldType::Bool(_) => ("bool", None, None),
+            FieldType::Date(_) => ("date", None, None),
+            FieldType::Facet(_) => ("facet", None, None),
+            FieldType::Bytes(_) => ("bytes", None, None),
+            FieldType::IpAddr(_) => ("ip_addr", None, None),
+        };
+        FieldSummary {
+            name: field_entry.name().to_string(),
+            field_type: field_type_label,
+            stored: field_entry.is_stored(),
+            indexed: field_entry.is_indexed(),
+            fast: field_entry.is_fast(),
+            tokenizer: indexing_options.map(|options| options.tokenizer().to_string()),
+            record_option: field_type.index_record_option().map(record_option_label),
+            fast_tokenizer: fast_tokenizer.map(str::to_string),
+        }
+    }
+
+    /// Named values of the options, in display order.
+    pub fn options(&self) -> Vec<(&'static str, String)> {
+        let optional = |value: Option<&str>| value.unwrap_or("-").to_string();
+        vec![
+            ("type", self.field_type.to_string()),
+            ("stored", self.stored.to_string()),
+            ("indexed", self.indexed.to_string()),
+            ("fast", self.fast.to_string()),
+            ("tokenizer", optional(self.tokenizer.as_deref())),
+            ("record", optional(self.record_option)),
+            ("fast tokenizer", optional(self.fast_tokenizer.as_deref())),
+        ]
+    }
+}
+
+fn record_option_label(record_option: IndexRecordOption) -> &'static str {
+    match record_option {
+        IndexRecordOption::Basic => "basic",
+        IndexRecordOption::WithFreqs => "freq",
+        IndexRecordOption::WithFreqsAndPositions => "position",
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum FieldDiff {
+    Added(FieldSummary),
+    Removed(FieldSummary),
+    Changed {
+        name: String,
+        changes: Vec<(&'static str, String, String)>,
+    },
+}
+
+/// Compares the fields of two schemas by name.
+///
+/// Removed fields come first, in the order of the left schema, followed by
+/// the changed and added fields in the order of the right schema.
+fn diff_schemas(left: &Schema, right: &Schema) -> Vec<FieldDiff> {
+    let mut field_diffs = Vec::new();
+    for (_, left_entry) in left.fields() {
+        if right.get_field(left_entry.name()).is_err() {
+            field_diffs.push(FieldDiff::Removed(FieldSummary::new(left_entry)));
+        }
+    }
+    for (_, right_entry) in right.fields() {
+        let right_summary = FieldSummary::new(right_entry);
+        let Ok(left_field) = left.get_field(right_entry.name()) else {
+            field_diffs.push(FieldDiff::Added(right_summary));
+            continue;
+        };
+        let left_summary = FieldSummary::new(left.get_field_entry(left_field));
+        let changes: Vec<(&'static str, String, String)> = left_summary
+            .options()
+            .into_iter()
+            .zip(right_summary.options())
+            .filter(|((_, left_value), (_, right_value))| left_value != right_value)
+            .map(|((option, left_value), (_, right_value))| (option, left_value, right_value))
+            .collect();
+        if !changes.is_empty() {
+            field_diffs.push(FieldDiff::Changed {
+                name: right_summary.name,
+                changes,
+            });
+        }
+    }
+    field_diffs
+}
+
+/// Only stored fields can be read back from an index.
+pub fn check_all_fields_stored(schema: &Schema) -> tantivy::Result<()> {
+    let unstored_fields: Vec<String> = schema
+        .fields()
+        .filter(|(_, field_entry)| !field_entry.is_stored())
+        .map(|(_, field_entry)| format!("`{}`", field_entry.name()))
+        .collect();
+    if unstored_fields.is_empty() {
+        return Ok(());
+    }
+    Err(TantivyError::SchemaError(format!(
+        "Fields {} are not stored: their values cannot be read back from the index and would \
+         be lost. Recreate the index from the source documents instead.",
+        unstored_fields.join(", ")
+    )))
+}
+
+/// Adds all the alive documents of `index` to `index_writer`, as they
+/// are stored in the doc store, and returns the number of documents copied.
+pub fn copy_stored_documents(
+    index: &Index,
+    index_writer: &mut IndexWriter,
+) -> tantivy::Result<u64> {
+    let searcher = index.reader()?.searcher();
+    let mut num_docs = 0;
+    for segment_reader in searcher.segment_readers() {
+        let store_reader = segment_reader.get_store_reader(100)?;
+        for doc_res in store_reader.iter::<TantivyDocument>(segment_reader.alive_bitset()) {
+            index_writer.add_document(doc_res?)?;
+            num_docs += 1;
+        }
+    }
+    Ok(num_docs)
+}
+
+/// Makes `directory` absolute by canonicalizing its parent, leaving its last
+/// component untouched.
+fn absolute_directory(directory: &Path) -> io::Result<PathBuf> {
+    let file_name = directory.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{:?} is not an index directory", directory),
+        )
+    })?;
+    let parent = match directory.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    Ok(parent.canonicalize()?.join(file_name))
+}
+
+fn sibling_directory(directory: &Path, suffix: &str) -> PathBuf {
+    let mut directory_name = directory.file_name().unwrap_or_default().to_os_string();
+    directory_name.push(format!(".{}", suffix));
+    directory.with_file_name(directory_name)
+}
+
+/// Replaces the index of `directory` by the one of `new_directory`.
+///
+/// On Unix, `directory` is turned into a symbolic link to a versioned sibling
+/// directory, so that the following swaps replace the link with a single
+/// atomic rename: a reader opening the index sees either the previous index
+/// or the new one. A plain directory cannot be atomically replaced, so the
+/// first swap, and every swap on other platforms, leaves `directory` missing
+/// between two renames.
+fn swap_directories(directory: &Path, new_directory: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        let versioned_directory = free_versioned_directory(directory);
+        fs::rename(new_directory, &versioned_directory).map_err(|e| {
+            swap_error(
+                e,
+                format!(
+                    "Failed to move the new index from {:?} to {:?}",
+                    new_directory, versioned_directory
+                ),
+            )
+        })?;
+        // The link is relative, so that the directories can be moved together.
+        let link_target = versioned_directory.file_name().unwrap();
+        if fs::symlink_metadata(directory)?.file_type().is_symlink() {
+            let previous_directory = fs::canonicalize(directory)?;
+            let new_link = sibling_directory(directory, "link");
+            std::os::unix::fs::symlink(link_target, &new_link)?;
+            if let Err(e) = fs::rename(&new_link, directory) {
+                let _ = fs::remove_file(&new_link);
+                return Err(swap_error(
+                    e,
+                    format!(
+                        "Failed to point {:?} to the new index, which is left in {:?}",
+                        directory, versioned_directory
+                    ),
+                ));
+            }
+            return fs::remove_dir_all(&previous_directory).map_err(|e| {
+                swap_error(
+                    e,
+                    format!(
+                        "The index was replaced, but the previous one could not be removed \
+                         from {:?}",
+                        previous_directory
+                    ),
+                )
+            });
+        }
+        replace_directory(directory, &versioned_directory, || {
+            std::os::unix::fs::symlink(link_target, directory)
+        })
+    }
+    #[cfg(not(unix))]
+    replace_directory(directory, new_directory, || {
+        fs::rename(new_directory, directory)
+    })
+}
+
+/// Moves `directory` aside and calls `put_new_in_place` to replace it by the
+/// new index in `new_directory`, putting the previous index back on failure.
+fn replace_directory(
+    directory: &Path,
+    new_directory: &Path,
+    put_new_in_place: impl FnOnce() -> io::Result<()>,
+) -> io::Result<()> {
+    let old_directory = sibling_directory(directory, "old");
+    fs::rename(directory, &old_directory).map_err(|e| {
+        swap_error(
+            e,
+            format!(
+                "Failed to move the index from {:?} to {:?}, the new index is left in {:?}",
+                directory, old_directory, new_directory
+            ),
+        )
+    })?;
+    if let Err(e) = put_new_in_place() {
+        let message = match fs::rename(&old_directory, directory) {
+            Ok(()) => format!(
+                "Failed to put the new index in place, the new index is left in {:?}",
+                new_directory
+            ),
+            Err(rollback_error) => format!(
+                "Failed to put the new index in place, and to put the previous one back \
+                 ({}): the previous index is left in {:?} and the new index in {:?}",
+                rollback_error, old_directory, new_directory
+            ),
+        };
+        return Err(swap_error(e, message));
+    }
+    fs::remove_dir_all(&old_directory).map_err(|e| {
+        swap_error(
+            e,
+            format!(
+                "The index was replaced, but the previous one could not be removed from {:?}",
+                old_directory
+            ),
+        )
+    })
+}
+
+/// The first of `directory.v1`, `directory.v2`... which does not exist.
+#[cfg(unix)]
+fn free_versioned_directory(directory: &Path) -> PathBuf {
+    (1..)
+        .map(|version| sibling_directory(directory, &format!("v{}", version)))
+        .find(|versioned_directory| fs::symlink_metadata(versioned_directory).is_err())
+        .unwrap()
+}
+
+fn swap_error(error: io::Error, message: String) -> io::Error {
+    io::Error::new(error.kind(), format!("{}: {}", message, error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tantivy::schema::{FAST, STORED, STRING, TEXT};
+
+    #[test]
+    fn unstored_fields_are_reported() {
+        let mut schema_builder = Schema::builder();
+        schema_builder.add_text_field("title", TEXT | STORED);
+        schema_builder.add_text_field("body", TEXT);
+        let err = check_all_fields_stored(&schema_builder.build()).unwrap_err();
+        assert!(err.to_string().contains("`body`"));
+        assert!(!err.to_string().contains("`title`"));
+    }
+
+    #[test]
+    fn diff_reports_added_removed_and_changed_fields() {
+        let mut left_builder = Schema::builder();
+        left_builder.add_text_field("title", TEXT | STORED);
+        left_builder.add_text_field("url", STRING | STORED);
+        left_builder.add_u64_field("views", STORED);
+        let mut right_builder = Schema::builder();
+        right_builder.add_text_field("title", TEXT | STORED);
+        right_builder.add_u64_field("views", STORED | FAST);
+        right_builder.add_text_field("body", TEXT);
+
+        let field_diffs = diff_schemas(&left_builder.build(), &right_builder.build());
+        assert_eq!(field_diffs.len(), 3);
+        assert!(matches!(&field_diffs[0], FieldDiff::Removed(field) if field.name == "url"));
+        assert_eq!(
+            field_diffs[1],
+            FieldDiff::Changed {
+                name: "views".to_string(),
+                changes: vec![("fast", "false".to_string(), "true".to_string())],
+            }
+        );
+        assert!(matches!(&field_diffs[2], FieldDiff::Added(field) if field.name == "body"));
+    }
+
+    #[test]
+    fn field_summary_of_text_field() {
+        let mut schema_builder = Schema::builder();
+        let title = schema_builder.add_text_field("title", TEXT | STORED);
+        let schema = schema_builder.build();
+        let summary = FieldSummary::new(schema.get_field_entry(title));
+        assert_eq!(summary.field_type, "text");
+        assert_eq!(summary.tokenizer.as_deref(), Some("default"));
+        assert_eq!(summary.record_option, Some("position"));
+        assert!(summary.stored && summary.indexed && !summary.fast);
+    }
+
+    #[test]
+    fn sibling_directory_keeps_parent() {
+        assert_eq!(
+            sibling_directory(Path::new("/data/wiki"), "old"),
+            PathBuf::from("/data/wiki.old")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn add_field_swaps_a_symlink() {
+        let root =
+            std::env::temp_dir().join(format!("tantivy-cli-add-field-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let directory = root.join("index");
+        fs::create_dir_all(&directory).unwrap();
+        let mut schema_builder = Schema::builder();
+        let title = schema_builder.add_text_field("title", TEXT | STORED);
+        let index = Index::create_in_dir(&directory, schema_builder.build()).unwrap();
+        let mut index_writer: IndexWriter = index.writer(15_000_000).unwrap();
+        index_writer
+            .add_document(tantivy::doc!(title => "paris"))
+            .unwrap();
+        index_writer.commit().unwrap();
+        drop(index_writer);
+        drop(index);
+
+        for field_name in ["body", "url"] {
+            run_add_field(&directory, |schema_builder, _, _| {
+                schema_builder.add_text_field(field_name, TEXT | STORED);
+            })
+            .unwrap();
+        }
+        assert_eq!(
+            fs::read_link(&directory).unwrap(),
+            PathBuf::from("index.v2")
+        );
+        let mut entries: Vec<_> = fs::read_dir(&root)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        entries.sort();
+        assert_eq!(entries, ["index", "index.v2"]);
+        let index = open_index(&directory).unwrap();
+        let schema = index.schema();
+        assert!(schema.get_field("body").is_ok() && schema.get_field("url").is_ok());
+        assert_eq!(index.reader().unwrap().searcher().num_docs(), 1);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
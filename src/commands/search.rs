@@ -1,34 +1,594 @@
 use clap::ArgMatches;
+use serde::Serialize;
+use std::collections::BTreeMap;
 use std::convert::From;
+use std::fs;
 use std::io::{self, ErrorKind, Write};
 use std::path::Path;
 use std::path::PathBuf;
-use std::process;
-use tantivy::aggregation::agg_req::Aggregations;
+use std::str::FromStr;
+use std::thread;
 use tantivy::aggregation::AggContextParams;
-use tantivy::aggregation::AggregationCollector;
-use tantivy::query::{EnableScoring, QueryParser};
+use tantivy::collector::sort_key::{Comparator, ComparatorEnum};
+use tantivy::collector::{Collector, Count, TopDocs};
+use tantivy::fastfield::FastValue;
+use tantivy::query::{EnableScoring, Explanation, Query};
 use tantivy::schema::Field;
 use tantivy::schema::FieldType;
-use tantivy::Document;
+use tantivy::schema::NamedFieldDocument;
+use tantivy::schema::{OwnedValue, Schema};
+use tantivy::TantivyError;
+use tantivy::TERMINATED;
 use tantivy::{self, TantivyDocument};
-use tantivy::{Index, TERMINATED};
+use tantivy::{DateTime, DocAddress, Order, Score, Searcher};
+
+use crate::commands::aggregation::{
+    aggregate_indexes, parse_aggregations, write_aggregation_tables,
+};
+use crate::commands::batch::{run_batch_search, BatchOptions};
+use crate::commands::explain::{explanation_tree, ExplainFormat};
+use crate::commands::facets::{
+    merge_facet_counts, write_facet_tables, FacetCountsByPath, FacetPaths,
+};
+use crate::commands::more_like_this::{parse_doc_address, LikeQuery, LikeTarget};
+use crate::commands::multi_index::{merge_top_docs, open_indexes};
+use crate::commands::output::{
+    csv_cell, flatten_named_doc, terminal_width, write_csv_record, write_fitted_table,
+};
+use crate::commands::projection::FieldProjection;
+use crate::commands::query_settings::{index_query_parser, QuerySettings};
+use crate::commands::snippets::{SnippetFormat, Snippets};
 
 pub fn run_search_cli(matches: &ArgMatches) -> Result<(), String> {
-    let index_directory = PathBuf::from(matches.get_one::<String>("index").unwrap());
-    let query = matches.get_one::<String>("query").unwrap();
-    let agg = matches.get_one::<String>("aggregation");
-    run_search(&index_directory, query, &agg).map_err(|e| format!("{:?}", e))
+    let index_directories: Vec<PathBuf> = matches
+        .get_many::<String>("index")
+        .unwrap()
+        .map(PathBuf::from)
+        .collect();
+    if let Some(queries_file) = matches.get_one::<String>("queries_file") {
+        let [index_directory] = index_directories.as_slice() else {
+            return Err("--queries-file searches a single index".to_string());
+        };
+        return run_batch_search_cli(index_directory, Path::new(queries_file), matches);
+    }
+    let query = match matches.get_one::<String>("query") {
+        Some(query) => SearchQuery::Parsed(query),
+        None => {
+            let target = match matches.get_one::<String>("like_doc") {
+                Some(doc_address) => LikeTarget::Doc(parse_doc_address(doc_address)?),
+                None => LikeTarget::Text(matches.get_one::<String>("like_text").unwrap().clone()),
+            };
+            SearchQuery::Like(LikeQuery {
+                target,
+                fields: matches
+                    .get_one::<String>("like_fields")
+                    .map(|fields| {
+                        fields
+                            .split(',')
+                            .map(|field| field.trim().to_string())
+                            .filter(|field| !field.is_empty())
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                min_doc_frequency: matches.get_one::<u64>("min_doc_freq").copied(),
+                min_term_frequency: matches.get_one::<usize>("min_term_freq").copied(),
+                max_query_terms: matches.get_one::<usize>("max_query_terms").copied(),
+            })
+        }
+    };
+    let agg = match matches.get_one::<String>("agg_file") {
+        Some(agg_file) => Some(
+            fs::read_to_string(agg_file)
+                .map_err(|e| format!("Failed to read {}: {}", agg_file, e))?,
+        ),
+        None => matches.get_one::<String>("aggregation").cloned(),
+    };
+    let search_mode = if matches.get_flag("all") {
+        SearchMode::All
+    } else if matches.get_flag("count") {
+        SearchMode::Count
+    } else {
+        let limit = *matches.get_one::<usize>("limit").unwrap();
+        if limit == 0 {
+            return Err("The limit must be greater than 0".to_string());
+        }
+        let offset = *matches.get_one::<usize>("offset").unwrap();
+        check_top_docs_window(limit, offset)?;
+        let sort_by = matches
+            .get_one::<String>("sort_by")
+            .map(|sort_by| sort_by.parse::<SortBy>())
+            .transpose()?;
+        let snippet_fields: Vec<String> = matches
+            .get_many::<String>("snippet")
+            .map(|fields| fields.cloned().collect())
+            .unwrap_or_default();
+        let snippet_format = match matches
+            .get_one::<String>("snippet_format")
+            .unwrap()
+            .as_str()
+        {
+            "ansi" => SnippetFormat::Ansi,
+            _ => SnippetFormat::Html,
+        };
+        let explain =
+            matches.get_one::<String>("explain").map(|explain_format| {
+                match explain_format.as_str() {
+                    "tree" => ExplainFormat::Tree,
+                    _ => ExplainFormat::Json,
+                }
+            });
+        SearchMode::TopK {
+            limit,
+            offset,
+            sort_by,
+            snippet_fields,
+            snippet_format,
+            explain,
+            facets: matches
+                .get_many::<String>("facet")
+                .map(|facets| facets.cloned().collect())
+                .unwrap_or_default(),
+        }
+    };
+    let query_settings = QuerySettings::from_matches(matches)?;
+    let lenient = matches.get_flag("lenient");
+    let fields: Option<Vec<String>> = matches.get_one::<String>("fields").map(|fields| {
+        fields
+            .split(',')
+            .map(|field| field.trim().to_string())
+            .filter(|field| !field.is_empty())
+            .collect()
+    });
+    let format = match matches.get_one::<String>("output").unwrap().as_str() {
+        "json" => OutputFormat::Json,
+        "csv" => OutputFormat::Csv,
+        "table" => OutputFormat::Table,
+        _ => OutputFormat::Ndjson,
+    };
+    check_output_format(format, agg.is_some(), &search_mode)?;
+    let output = Output { format, fields };
+    match run_search(
+        &index_directories,
+        query,
+        agg.as_deref(),
+        search_mode,
+        query_settings,
+        lenient,
+        output,
+    ) {
+        Err(TantivyError::IoError(e)) if e.kind() == ErrorKind::BrokenPipe => Ok(()),
+        res => res.map_err(|e| format!("{:?}", e)),
+    }
 }
 
-fn run_search(
-    directory: &Path,
-    query: &str,
-    agg: &std::option::Option<&String>,
-) -> tantivy::Result<()> {
-    let index = Index::open_in_dir(directory)?;
-    let schema = index.schema();
-    let default_fields: Vec<Field> = schema
+fn run_batch_search_cli(
+    index_directory: &Path,
+    queries_file: &Path,
+    matches: &ArgMatches,
+) -> Result<(), String> {
+    let limit = *matches.get_one::<usize>("limit").unwrap();
+    if limit == 0 {
+        return Err("The limit must be greater than 0".to_string());
+    }
+    let offset = *matches.get_one::<usize>("offset").unwrap();
+    check_top_docs_window(limit, offset)?;
+    let num_threads = match matches.get_one::<usize>("num_threads") {
+        Some(num_threads) => *num_threads,
+        None => thread::available_parallelism().map_or(1, usize::from),
+    };
+    let options = BatchOptions {
+        limit,
+        offset,
+        sort_by: matches
+            .get_one::<String>("sort_by")
+            .map(|sort_by| sort_by.parse::<SortBy>())
+            .transpose()?,
+        lenient: matches.get_flag("lenient"),
+        num_threads,
+    };
+    let fields: Option<Vec<String>> = matches.get_one::<String>("fields").map(|fields| {
+        fields
+            .split(',')
+            .map(|field| field.trim().to_string())
+            .filter(|field| !field.is_empty())
+            .collect()
+    });
+    let query_settings = QuerySettings::from_matches(matches)?;
+    match run_batch_search(
+        index_directory,
+        queries_file,
+        options,
+        query_settings,
+        fields,
+    ) {
+        Err(TantivyError::IoError(e)) if e.kind() == ErrorKind::BrokenPipe => Ok(()),
+        res => res.map_err(|e| format!("{:?}", e)),
+    }
+}
+
+/// Largest `offset + limit` of a search. The collectors allocate room for
+/// that many documents upfront.
+const MAX_TOP_DOCS: usize = 1_000_000;
+
+/// Rejects an `offset` and `limit` whose sum is larger than `MAX_TOP_DOCS`.
+pub fn check_top_docs_window(limit: usize, offset: usize) -> Result<(), String> {
+    match offset.checked_add(limit) {
+        Some(num_docs) if num_docs <= MAX_TOP_DOCS => Ok(()),
+        _ => Err(format!(
+            "The offset plus the limit must be at most {}",
+            MAX_TOP_DOCS
+        )),
+    }
+}
+
+/// Rejects the options whose output cannot be written in `format`.
+fn check_output_format(
+    format: OutputFormat,
+    has_agg: bool,
+    search_mode: &SearchMode,
+) -> Result<(), String> {
+    let is_tabular = matches!(format, OutputFormat::Csv | OutputFormat::Table);
+    if has_agg && format == OutputFormat::Csv {
+        return Err("Aggregation results can only be written as JSON or as tables".to_string());
+    }
+    if let SearchMode::TopK {
+        snippet_fields,
+        snippet_format,
+        explain,
+        ..
+    } = search_mode
+    {
+        if is_tabular && (!snippet_fields.is_empty() || explain.is_some()) {
+            return Err(
+                "Snippets and explanations cannot be written as csv or as a table".to_string(),
+            );
+        }
+        let has_trailing_lines = (!snippet_fields.is_empty()
+            && *snippet_format == SnippetFormat::Ansi)
+            || *explain == Some(ExplainFormat::Tree);
+        if format == OutputFormat::Json && has_trailing_lines {
+            return Err(
+                "ANSI snippets and explanation trees can only be written as ndjson".to_string(),
+            );
+        }
+    }
+    Ok(())
+}
+
+/// How the hits are written on stdout.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum OutputFormat {
+    /// One JSON object per line.
+    Ndjson,
+    /// A JSON array.
+    Json,
+    /// CSV, with a header naming the fields.
+    Csv,
+    /// A table, with aligned columns, fitted to the width of the terminal.
+    Table,
+}
+
+/// The format of the hits and the fields they hold, all the stored fields if
+/// `fields` is `None`.
+struct Output {
+    format: OutputFormat,
+    fields: Option<Vec<String>>,
+}
+
+/// Writes the hits in an output format. Formats which cannot be streamed
+/// are written by `finish`.
+struct HitWriter<W: Write> {
+    wrt: W,
+    format: OutputFormat,
+    /// Fields written as CSV or table columns.
+    columns: Vec<String>,
+    json_hits: Vec<serde_json::Value>,
+    rows: Vec<Vec<String>>,
+}
+
+impl<W: Write> HitWriter<W> {
+    fn new(mut wrt: W, format: OutputFormat, columns: Vec<String>) -> io::Result<HitWriter<W>> {
+        if format == OutputFormat::Csv {
+            write_csv_record(&mut wrt, &columns)?;
+        }
+        Ok(HitWriter {
+            wrt,
+            format,
+            columns,
+            json_hits: Vec::new(),
+            rows: Vec::new(),
+        })
+    }
+
+    /// Writes a hit, whose fields are `doc`. In ndjson, `trailing_lines`
+    /// are written, indented, after the hit.
+    fn write<T: Serialize>(
+        &mut self,
+        hit: &T,
+        doc: &NamedFieldDocument,
+        trailing_lines: &[String],
+    ) -> io::Result<()> {
+        match self.format {
+            OutputFormat::Ndjson => {
+                writeln!(self.wrt, "{}", serde_json::to_string(hit)?)?;
+                for line in trailing_lines {
+                    writeln!(self.wrt, "  {}", line)?;
+                }
+            }
+            OutputFormat::Json => self.json_hits.push(serde_json::to_value(hit)?),
+            OutputFormat::Csv | OutputFormat::Table => {
+                let doc_json = flatten_named_doc(NamedFieldDocument(doc.0.clone()));
+                let cells: Vec<String> = self
+                    .columns
+                    .iter()
+                    .map(|field_name| csv_cell(doc_json.get(field_name)))
+                    .collect();
+                if self.format == OutputFormat::Csv {
+                    write_csv_record(&mut self.wrt, &cells)?;
+                } else {
+                    self.rows.push(cells);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the hits which were not streamed. In JSON and ndjson, the
+    /// `facet_counts`, if any, are written as a `facets` object: after the
+    /// hits in ndjson, and next to a `hits` array in JSON.
+    fn finish(mut self, facet_counts: Option<&FacetCountsByPath>) -> io::Result<()> {
+        match self.format {
+            OutputFormat::Ndjson => {
+                if let Some(facet_counts) = facet_counts {
+                    let facets = serde_json::json!({ "facets": facet_counts });
+                    writeln!(self.wrt, "{}", facets)?;
+                }
+            }
+            OutputFormat::Json => {
+                let json = match facet_counts {
+                    Some(facet_counts) => serde_json::json!({
+                        "hits": self.json_hits,
+                        "facets": facet_counts,
+                    }),
+                    None => serde_json::Value::Array(self.json_hits),
+                };
+                writeln!(self.wrt, "{}", serde_json::to_string_pretty(&json)?)?;
+            }
+            OutputFormat::Table => {
+                write_fitted_table(&mut self.wrt, &self.columns, &self.rows, terminal_width())?;
+            }
+            OutputFormat::Csv => {}
+        }
+        self.wrt.flush()
+    }
+}
+
+/// What `run_search` searches for.
+enum SearchQuery<'a> {
+    /// A query parsed with the query parser of each index.
+    Parsed(&'a str),
+    /// A more-like-this query. Its terms are drawn from the first index, where
+    /// a liked document is read.
+    Like(LikeQuery),
+}
+
+/// How the documents matching the query are returned.
+enum SearchMode {
+    /// Every matching document, unranked, in segment order.
+    All,
+    /// Only the number of matching documents.
+    Count,
+    /// The `limit` best ranked documents after skipping `offset` of them,
+    /// ranked by score unless `sort_by` is set, with a snippet of each of the
+    /// `snippet_fields` and, if `explain` is set, the explanation of their score.
+    /// The children of the `facets` paths are counted over all the matches.
+    TopK {
+        limit: usize,
+        offset: usize,
+        sort_by: Option<SortBy>,
+        snippet_fields: Vec<String>,
+        snippet_format: SnippetFormat,
+        explain: Option<ExplainFormat>,
+        facets: Vec<String>,
+    },
+}
+
+#[derive(Serialize)]
+pub struct Hit {
+    /// Name of the index of the hit, when searching several indexes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<Score>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort_value: Option<OwnedValue>,
+    pub doc_address: DocAddress,
+    pub doc: NamedFieldDocument,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub snippets: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub explanation: Option<Explanation>,
+}
+
+/// A fast field to rank the hits by, parsed from `field[:asc|desc]`.
+///
+/// The order defaults to descending.
+#[derive(Debug, PartialEq)]
+pub struct SortBy {
+    pub field: String,
+    pub order: Order,
+}
+
+impl FromStr for SortBy {
+    type Err = String;
+
+    fn from_str(sort_by: &str) -> Result<SortBy, String> {
+        let (field, order) = match sort_by.rsplit_once(':') {
+            Some((field, "asc")) => (field, Order::Asc),
+            Some((field, "desc")) => (field, Order::Desc),
+            Some((_, order)) => {
+                return Err(format!(
+                    "Unknown sort order `{}`, expected `asc` or `desc`",
+                    order
+                ))
+            }
+            None => (sort_by, Order::Desc),
+        };
+        if field.is_empty() {
+            return Err("The field to sort by is missing".to_string());
+        }
+        Ok(SortBy {
+            field: field.to_string(),
+            order,
+        })
+    }
+}
+
+/// A ranked document: its score, or the value it was sorted by, and its address.
+pub type RankedDoc = (Option<Score>, Option<OwnedValue>, DocAddress);
+
+/// Hits sorted by a fast field, with the value each document was sorted by,
+/// and the fruit of the collector run alongside.
+pub type SortedHits<F> = (Vec<(Option<OwnedValue>, DocAddress)>, F);
+
+/// Runs `query`, ranking the hits by the fast field `sort_by.field`, and
+/// collecting the matching documents with `collector` in the same pass.
+///
+/// Only u64, i64, f64 and date fast fields can be used to sort.
+pub fn search_sorted_by<C: Collector>(
+    searcher: &Searcher,
+    query: &dyn Query,
+    sort_by: &SortBy,
+    limit: usize,
+    offset: usize,
+    collector: C,
+) -> tantivy::Result<SortedHits<C::Fruit>> {
+    let schema = searcher.schema();
+    let field_entry = schema.get_field_entry(schema.get_field(&sort_by.field)?);
+    if !field_entry.is_fast() {
+        return Err(TantivyError::InvalidArgument(format!(
+            "Field `{}` is not a fast field and cannot be used to sort",
+            sort_by.field
+        )));
+    }
+    match field_entry.field_type() {
+        FieldType::U64(_) => top_by_fast_field(
+            searcher,
+            query,
+            sort_by,
+            limit,
+            offset,
+            collector,
+            OwnedValue::U64,
+        ),
+        FieldType::I64(_) => top_by_fast_field(
+            searcher,
+            query,
+            sort_by,
+            limit,
+            offset,
+            collector,
+            OwnedValue::I64,
+        ),
+        FieldType::F64(_) => top_by_fast_field(
+            searcher,
+            query,
+            sort_by,
+            limit,
+            offset,
+            collector,
+            OwnedValue::F64,
+        ),
+        FieldType::Date(_) => top_by_fast_field::<DateTime, C>(
+            searcher,
+            query,
+            sort_by,
+            limit,
+            offset,
+            collector,
+            OwnedValue::Date,
+        ),
+        field_type => Err(TantivyError::InvalidArgument(format!(
+            "Field `{}` is of type {:?}, only u64, i64, f64 and date fields can be used to sort",
+            sort_by.field,
+            field_type.value_type()
+        ))),
+    }
+}
+
+fn top_by_fast_field<T, C>(
+    searcher: &Searcher,
+    query: &dyn Query,
+    sort_by: &SortBy,
+    limit: usize,
+    offset: usize,
+    collector: C,
+    to_value: fn(T) -> OwnedValue,
+) -> tantivy::Result<SortedHits<C::Fruit>>
+where
+    T: FastValue,
+    C: Collector,
+    ComparatorEnum: Comparator<Option<T>>,
+{
+    let (top_docs, fruit) = searcher.search(
+        query,
+        &(
+            TopDocs::with_limit(limit)
+                .and_offset(offset)
+                .order_by_fast_field::<T>(&sort_by.field, sort_by.order),
+            collector,
+        ),
+    )?;
+    let top_docs = top_docs
+        .into_iter()
+        .map(|(sort_value, doc_address)| (sort_value.map(to_value), doc_address))
+        .collect();
+    Ok((top_docs, fruit))
+}
+
+/// Runs `query`, ranking the hits by score, or by a fast field if `sort_by`
+/// is set, and collecting the matching documents with `collector` in the
+/// same pass.
+pub fn search_top_docs<C: Collector>(
+    searcher: &Searcher,
+    query: &dyn Query,
+    sort_by: Option<&SortBy>,
+    limit: usize,
+    offset: usize,
+    collector: C,
+) -> tantivy::Result<(Vec<RankedDoc>, C::Fruit)> {
+    match sort_by {
+        Some(sort_by) => {
+            let (top_docs, fruit) =
+                search_sorted_by(searcher, query, sort_by, limit, offset, collector)?;
+            let hits = top_docs
+                .into_iter()
+                .map(|(sort_value, doc_address)| (None, sort_value, doc_address))
+                .collect();
+            Ok((hits, fruit))
+        }
+        None => {
+            let (top_docs, fruit) = searcher.search(
+                query,
+                &(
+                    TopDocs::with_limit(limit)
+                        .and_offset(offset)
+                        .order_by_score(),
+                    collector,
+                ),
+            )?;
+            let hits = top_docs
+                .into_iter()
+                .map(|(score, doc_address)| (Some(score), None, doc_address))
+                .collect();
+            Ok((hits, fruit))
+        }
+    }
+}
+
+/// Indexed text fields, searched when a query does not target a specific field.
+pub fn default_search_fields(schema: &Schema) -> Vec<Field> {
+    schema
         .fields()
         .filter(|(_, field_entry)| match *field_entry.field_type() {
             FieldType::Str(ref text_field_options) => {
@@ -37,47 +597,310 @@ fn run_search(
             _ => false,
         })
         .map(|(field, _)| field)
-        .collect();
-    let query_parser = QueryParser::new(schema.clone(), default_fields, index.tokenizers().clone());
-    let query = query_parser.parse_query(query)?;
-    let searcher = index.reader()?.searcher();
-    let weight = query.weight(EnableScoring::enabled_from_searcher(&searcher))?;
+        .collect()
+}
+
+/// An index searched by `run_search`, with the query parsed with its own
+/// query settings.
+struct IndexSearch {
+    name: String,
+    searcher: Searcher,
+    query: Box<dyn Query>,
+}
+
+fn run_search(
+    directories: &[PathBuf],
+    query: SearchQuery,
+    agg: Option<&str>,
+    search_mode: SearchMode,
+    query_settings: QuerySettings,
+    lenient: bool,
+    output: Output,
+) -> tantivy::Result<()> {
+    let indexes = open_indexes(directories)?;
+    let schema = indexes[0].index.schema();
+    let (projection, columns) = match output.fields {
+        Some(fields) => (FieldProjection::new(&schema, &fields)?, fields),
+        None => {
+            let stored_fields = schema
+                .fields()
+                .filter(|(_, field_entry)| field_entry.is_stored())
+                .map(|(_, field_entry)| field_entry.name().to_string())
+                .collect();
+            (FieldProjection::all_stored(), stored_fields)
+        }
+    };
+    let mut query_errors: Vec<String> = Vec::new();
+    let mut searches: Vec<IndexSearch> = Vec::new();
+    for named_index in indexes {
+        let searcher = named_index.index.reader()?.searcher();
+        let query: Box<dyn Query> = match &query {
+            SearchQuery::Parsed(query) => {
+                let query_parser = index_query_parser(
+                    &named_index.index,
+                    &named_index.directory,
+                    query_settings.clone(),
+                )?;
+                if lenient {
+                    let (query, errors) = query_parser.parse_query_lenient(query);
+                    for error in errors {
+                        let error = error.to_string();
+                        if !query_errors.contains(&error) {
+                            query_errors.push(error);
+                        }
+                    }
+                    query
+                } else {
+                    query_parser.parse_query(query).map_err(|e| {
+                        TantivyError::InvalidArgument(format!(
+                            "Invalid query: {}. Use --lenient to run the valid part of the query",
+                            e
+                        ))
+                    })?
+                }
+            }
+            SearchQuery::Like(like_query) => match searches.first() {
+                Some(first_search) => first_search.query.box_clone(),
+                None => Box::new(like_query.query(&searcher)?),
+            },
+        };
+        searches.push(IndexSearch {
+            name: named_index.name,
+            searcher,
+            query,
+        });
+    }
+    for error in query_errors {
+        eprintln!("Ignored invalid part of the query: {}", error);
+    }
+    // Hits are only tagged with the name of their index if there are several.
+    let is_multi_index = searches.len() > 1;
+    let like_query = match &query {
+        SearchQuery::Like(like_query) => Some(like_query),
+        SearchQuery::Parsed(_) => None,
+    };
 
     let mut stdout = io::BufWriter::new(io::stdout());
     if let Some(agg) = agg {
-        let agg_req: Aggregations = serde_json::from_str(agg).unwrap();
-        let collector = AggregationCollector::from_aggs(agg_req, AggContextParams::default());
-        let agg_res = searcher.search(&query, &collector).unwrap();
-
-        if let Err(e) = writeln!(
-            stdout,
-            "{}",
-            serde_json::to_string_pretty(&agg_res).unwrap()
-        ) {
-            if e.kind() != ErrorKind::BrokenPipe {
-                eprintln!("{}", e);
-                process::exit(1)
+        let agg_req = parse_aggregations(agg)?;
+        let index_searches: Vec<(&Searcher, &dyn Query)> = searches
+            .iter()
+            .map(|search| (&search.searcher, search.query.as_ref()))
+            .collect();
+        let agg_res = aggregate_indexes(&index_searches, agg_req, AggContextParams::default())?;
+        if output.format == OutputFormat::Table {
+            write_aggregation_tables(&mut stdout, &agg_res)?;
+        } else {
+            writeln!(stdout, "{}", serde_json::to_string_pretty(&agg_res)?)?;
+        }
+        stdout.flush()?;
+        return Ok(());
+    }
+    if let SearchMode::Count = search_mode {
+        // The `Count` collector does not need the doc store.
+        let mut count = 0;
+        for search in &searches {
+            count += search.searcher.search(&search.query, &Count)?;
+        }
+        writeln!(stdout, "{}", count)?;
+        stdout.flush()?;
+        return Ok(());
+    }
+    let mut hit_writer = HitWriter::new(stdout, output.format, columns)?;
+    if let SearchMode::TopK {
+        limit,
+        offset,
+        sort_by,
+        snippet_fields,
+        snippet_format,
+        explain,
+        facets,
+    } = search_mode
+    {
+        let facet_paths = FacetPaths::parse(&schema, &facets)?;
+        let mut count = 0;
+        let mut facet_counts = FacetCountsByPath::new();
+        let mut top_docs_per_index = Vec::new();
+        let mut snippets_per_index = Vec::new();
+        for (index_ord, search) in searches.iter().enumerate() {
+            let (facet_collector, facet_handles) = facet_paths.collector();
+            // The liked document is read from the first index.
+            let like_query = like_query.filter(|_| index_ord == 0);
+            // Each index returns its `offset + limit` best documents, which are
+            // then merged, and one more in place of the liked document.
+            let (mut top_docs, (mut index_count, facet_fruits)) = search_top_docs(
+                &search.searcher,
+                &search.query,
+                sort_by.as_ref(),
+                offset + limit + usize::from(like_query.is_some()),
+                0,
+                (Count, facet_collector),
+            )?;
+            if like_query.is_some_and(|like_query| like_query.remove_liked_doc(&mut top_docs)) {
+                index_count -= 1;
+            }
+            count += index_count;
+            merge_facet_counts(&mut facet_counts, facet_handles.counts(facet_fruits));
+            top_docs_per_index.push(top_docs);
+            snippets_per_index.push(Snippets::create(
+                &search.searcher,
+                &search.query,
+                &snippet_fields,
+                snippet_format,
+            )?);
+        }
+        let hits = merge_top_docs(top_docs_per_index, sort_by.as_ref(), limit, offset);
+        for (index_ord, (score, sort_value, doc_address)) in hits {
+            let IndexSearch {
+                name,
+                searcher,
+                query,
+            } = &searches[index_ord];
+            let snippets = &snippets_per_index[index_ord];
+            let doc: Option<TantivyDocument> =
+                if projection.needs_stored_doc() || !snippets.is_empty() {
+                    Some(searcher.doc(doc_address)?)
+                } else {
+                    None
+                };
+            let mut hit = Hit {
+                index: is_multi_index.then(|| name.clone()),
+                score,
+                sort_value,
+                doc_address,
+                doc: projection.named_doc(searcher, doc_address, doc.as_ref())?,
+                snippets: doc
+                    .as_ref()
+                    .map(|doc| snippets.render(doc))
+                    .unwrap_or_default(),
+                explanation: None,
+            };
+            // Escape sequences would be escaped in JSON, so ANSI snippets are
+            // written on their own lines, after the hit, as are explanation trees.
+            let ansi_snippets = match snippet_format {
+                SnippetFormat::Ansi => std::mem::take(&mut hit.snippets),
+                SnippetFormat::Html => BTreeMap::new(),
+            };
+            let mut trailing_lines: Vec<String> = ansi_snippets
+                .into_iter()
+                .map(|(field_name, snippet)| format!("{}: {}", field_name, snippet))
+                .collect();
+            if let Some(explain_format) = explain {
+                let explanation = query.explain(searcher, doc_address)?;
+                match explain_format {
+                    ExplainFormat::Json => hit.explanation = Some(explanation),
+                    ExplainFormat::Tree => trailing_lines
+                        .extend(explanation_tree(&explanation).lines().map(str::to_string)),
+                }
             }
+            hit_writer.write(&hit, &hit.doc, &trailing_lines)?;
         }
+        eprintln!("{} hits", count);
+        // Facet counts are written along with the hits in JSON, and as
+        // tables on stderr otherwise.
+        let is_json = matches!(output.format, OutputFormat::Json | OutputFormat::Ndjson);
+        if !facets.is_empty() && !is_json {
+            write_facet_tables(&mut io::stderr(), &facet_counts)?;
+        }
+        hit_writer.finish((!facets.is_empty() && is_json).then_some(&facet_counts))?;
     } else {
-        for segment_reader in searcher.segment_readers() {
-            let mut scorer = weight.scorer(segment_reader, 1.0)?;
-            let store_reader = segment_reader.get_store_reader(100)?;
-            while scorer.doc() != TERMINATED {
-                let doc_id = scorer.doc();
-                let doc: TantivyDocument = store_reader.get(doc_id)?;
-                let named_doc = doc.to_named_doc(&schema);
-                if let Err(e) = writeln!(stdout, "{}", serde_json::to_string(&named_doc).unwrap()) {
-                    if e.kind() != ErrorKind::BrokenPipe {
-                        eprintln!("{}", e);
-                        process::exit(1)
+        // The documents of each index are written in turn.
+        for (index_ord, search) in searches.iter().enumerate() {
+            let liked_doc = like_query
+                .filter(|_| index_ord == 0)
+                .and_then(LikeQuery::liked_doc);
+            let searcher = &search.searcher;
+            let weight = search
+                .query
+                .weight(EnableScoring::enabled_from_searcher(searcher))?;
+            for (segment_ord, segment_reader) in searcher.segment_readers().iter().enumerate() {
+                let mut scorer = weight.scorer(segment_reader, 1.0)?;
+                let store_reader = segment_reader.get_store_reader(100)?;
+                while scorer.doc() != TERMINATED {
+                    let doc_id = scorer.doc();
+                    let doc_address = DocAddress::new(segment_ord as u32, doc_id);
+                    if Some(doc_address) == liked_doc {
+                        scorer.advance();
+                        continue;
                     }
+                    let doc: Option<TantivyDocument> = if projection.needs_stored_doc() {
+                        Some(store_reader.get(doc_id)?)
+                    } else {
+                        None
+                    };
+                    let named_doc = projection.named_doc(searcher, doc_address, doc.as_ref())?;
+                    hit_writer.write(&named_doc, &named_doc, &[])?;
+                    scorer.advance();
                 }
-                scorer.advance();
             }
         }
+        hit_writer.finish(None)?;
     }
-    stdout.flush()?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn facet_counts_are_written_with_json_hits() {
+        let mut facet_counts = FacetCountsByPath::new();
+        facet_counts
+            .entry("category:/politics".to_string())
+            .or_default()
+            .insert("/politics/europe".to_string(), 2);
+        let hit = NamedFieldDocument(BTreeMap::new());
+        let written = |format: OutputFormat| {
+            let mut output = Vec::new();
+            let mut hit_writer = HitWriter::new(&mut output, format, Vec::new()).unwrap();
+            hit_writer.write(&hit, &hit, &[]).unwrap();
+            hit_writer.finish(Some(&facet_counts)).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+        let facets = serde_json::json!({"category:/politics": {"/politics/europe": 2}});
+        let ndjson = written(OutputFormat::Ndjson);
+        let lines: Vec<serde_json::Value> = ndjson
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                serde_json::json!({}),
+                serde_json::json!({ "facets": facets })
+            ]
+        );
+        let json: serde_json::Value = serde_json::from_str(&written(OutputFormat::Json)).unwrap();
+        assert_eq!(json, serde_json::json!({"hits": [{}], "facets": facets}));
+    }
+
+    #[test]
+    fn top_docs_window_is_bounded() {
+        assert!(check_top_docs_window(10, 0).is_ok());
+        assert!(check_top_docs_window(MAX_TOP_DOCS, 0).is_ok());
+        assert!(check_top_docs_window(MAX_TOP_DOCS, 1).is_err());
+        assert!(check_top_docs_window(10, usize::MAX).is_err());
+    }
+
+    #[test]
+    fn parse_sort_by() {
+        assert_eq!(
+            "views".parse::<SortBy>(),
+            Ok(SortBy {
+                field: "views".to_string(),
+                order: Order::Desc
+            })
+        );
+        assert_eq!(
+            "published:asc".parse::<SortBy>(),
+            Ok(SortBy {
+                field: "published".to_string(),
+                order: Order::Asc
+            })
+        );
+        assert!("views:up".parse::<SortBy>().is_err());
+        assert!(":asc".parse::<SortBy>().is_err());
+    }
+}
@@ -4,7 +4,28 @@
 /// and it takes the following query string argument
 ///
 /// - `q=` :    your query
-///  - `nhits`:  the number of hits that should be returned. (default to 10)
+///  - `nhits`:  the number of hits that should be returned. (default to 10,
+///    at most 10000, as is `offset`)
+///  - `sort`:   a fast field to sort the hits by instead of their score,
+///    as `field[:asc|desc]`. (descending by default)
+///  - `snippet`: stored text fields to add a highlighted html snippet of
+///    to each hit, separated by commas.
+///  - `explain`: with `explain=true`, each hit carries the explanation of
+///    its score.
+///  - `conjunction`: with `conjunction=true`, all the terms of the query
+///    must match, and with `conjunction=false` any of them, whatever the
+///    settings of the index.
+///  - `fuzzy`:  fields whose terms also match misspelled terms, as
+///    `field[:distance][:prefix][:no-transposition]`, separated by commas.
+///  - `fields`: stored or fast fields to return for each hit, separated by
+///    commas. (default to all the stored fields)
+///  - `lenient`: with `lenient=true`, the valid part of an invalid query is
+///    run, and the parsing errors are returned in `query_errors`.
+///  - `facet`: facet paths, as `[field:]/path` and separated by commas, whose
+///    children are counted over all the matching documents, in `facets`.
+///
+/// Invalid requests get a 400 response, with the reason in `error`, and the
+/// query parsing errors in `query_errors`.
 ///
 ///
 /// For instance, the following call should return the 20 most relevant
@@ -12,41 +33,142 @@
 ///
 ///     http://localhost:3000/api/?q=fulmicoton&nhits=20
 ///
+/// and the following one the 20 most viewed.
+///
+///     http://localhost:3000/api/?q=fulmicoton&nhits=20&sort=views:desc
+///
+/// /api/ also accepts POST requests, with the arguments as a JSON object,
+/// `snippet`, `fuzzy`, `fields` and `facet` being arrays. The object may hold an
+/// `aggs` aggregation request, which is run in the same pass as the search,
+/// its results being returned in `aggregations`. `nhits` may be 0 if there
+/// are aggregations or facets.
+///
+/// POST /api/aggregate/ takes the same object, with `aggs` required and
+/// `nhits` defaulting to 0, to only run the aggregations.
+///
+///     curl -X POST http://localhost:3000/api/aggregate/ \
+///         -d '{"q": "*", "aggs": {"langs": {"terms": {"field": "lang"}}}}'
+///
+/// The memory and the number of buckets used by the aggregations of a request
+/// are limited by `--agg-memory-limit` and `--agg-bucket-limit`.
+///
+/// /api/count/ only returns the number of documents matching a query, with
+/// the time it took to count them, without reading any document. It takes
+/// the `q`, `conjunction`, `fuzzy` and `lenient` arguments of /api/.
+///
+///     http://localhost:3000/api/count/?q=fulmicoton
+///
+/// /api/autocomplete/ returns the distinct stored values of a text field
+/// matching a prefix. The field should use a n-gram or edge n-gram tokenizer.
+///
+/// - `field=` : the field to complete
+/// - `q=` :     the prefix typed by the user
+/// - `n` :      the number of suggestions that should be returned. (default to 10,
+///   at most 100)
+///
+/// At most 10 matching documents are read per suggestion, so fewer distinct
+/// values may be returned when many documents share the same one.
+///
+///     http://localhost:3000/api/autocomplete/?field=title_prefix&q=barack&n=5
+///
+/// /api/mlt/ returns the documents most like a document, given by the
+/// `segment_ord` and `doc_id` of the `doc_address` of its hit, or like a text.
+///
+/// - `doc=` or `text=` : the document, as `segment:doc`, or the text. With
+///   several indexes, the document is read from the first one.
+/// - `like_fields`: the text fields the terms are drawn from and searched in,
+///   separated by commas. (default to all the indexed text fields)
+/// - `min_doc_freq`: terms found in fewer documents are ignored. (default to 5)
+/// - `min_term_freq`: terms occurring fewer times in the document or the
+///   text are ignored. (default to 2)
+/// - `max_query_terms`: the number of terms searched for. (default to 25)
+///
+/// It also takes the `nhits`, `offset`, `fields` and `explain` arguments of
+/// /api/.
+///
+///     http://localhost:3000/api/mlt/?doc=0:42&like_fields=body&nhits=5
+///
+/// The server may serve several indexes with the same schema, given by several
+/// `--index` options. Their hits are merged, and tagged with the name of their
+/// index in `index`.
+///
+use crate::commands::aggregation::aggregations_from_value;
+use crate::commands::facets::{merge_facet_counts, FacetCountsByPath, FacetPaths};
+use crate::commands::more_like_this::{parse_doc_address, LikeQuery, LikeTarget};
+use crate::commands::multi_index::{merge_top_docs, open_indexes};
+use crate::commands::projection::FieldProjection;
+use crate::commands::query_settings::{load_query_settings, parse_fuzzy, QuerySettings};
+use crate::commands::search::{search_top_docs, SortBy};
+use crate::commands::snippets::{SnippetFormat, Snippets};
 use crate::timer::TimerTree;
 use clap::ArgMatches;
+use iron::method::Method;
 use iron::mime::Mime;
 use iron::prelude::*;
 use iron::status;
 use iron::typemap::Key;
 use mount::Mount;
 use persistent::Read;
-use serde_derive::Serialize;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::convert::From;
 use std::error::Error;
 use std::fmt::{self, Debug};
-use std::path::Path;
+use std::io::Read as _;
 use std::path::PathBuf;
 use std::str::FromStr;
+use tantivy::aggregation::agg_req::Aggregations;
+use tantivy::aggregation::agg_result::AggregationResults;
+use tantivy::aggregation::intermediate_agg_result::IntermediateAggregationResults;
+use tantivy::aggregation::{
+    AggContextParams, AggregationLimitsGuard, DistributedAggregationCollector,
+};
 use tantivy::collector::{Count, TopDocs};
-use tantivy::query::QueryParser;
-use tantivy::schema::Field;
+use tantivy::query::{
+    BooleanQuery, Explanation, Occur, Query, QueryParser, QueryParserError, TermQuery,
+};
 use tantivy::schema::FieldType;
 use tantivy::schema::NamedFieldDocument;
 use tantivy::schema::Schema;
-use tantivy::Document;
-use tantivy::Index;
+use tantivy::schema::{IndexRecordOption, OwnedValue, Term, Value};
+use tantivy::tokenizer::TokenStream;
 use tantivy::IndexReader;
 use tantivy::TantivyDocument;
-use tantivy::{DocAddress, Score};
-use urlencoded::UrlEncodedQuery;
+use tantivy::{DocAddress, Score, Searcher};
+use tantivy::{Index, TantivyError};
+use urlencoded::{QueryMap, UrlEncodedQuery};
 
 pub fn run_serve_cli(matches: &ArgMatches) -> Result<(), String> {
-    let index_directory = PathBuf::from(matches.get_one::<String>("index").unwrap());
+    let index_directories: Vec<PathBuf> = matches
+        .get_many::<String>("index")
+        .unwrap()
+        .map(PathBuf::from)
+        .collect();
     let port = ArgMatches::get_one(matches, "port").unwrap_or(&3000usize);
     let fallback = "localhost".to_string();
     let host_str = matches.get_one::<String>("host").unwrap_or(&fallback);
     let host = format!("{}:{}", host_str, port);
-    run_serve(index_directory, &host).map_err(|e| format!("{:?}", e))
+    let query_settings = QuerySettings::from_matches(matches)?;
+    let aggregation_limits = AggregationLimits {
+        memory_limit: matches.get_one::<u64>("agg_memory_limit").copied(),
+        bucket_limit: matches.get_one::<u32>("agg_bucket_limit").copied(),
+    };
+    run_serve(
+        &index_directories,
+        &host,
+        query_settings,
+        aggregation_limits,
+    )
+    .map_err(|e| format!("{:?}", e))
+}
+
+/// Limits of the aggregations of a request. Tantivy's defaults apply to the
+/// unset ones.
+#[derive(Clone, Copy)]
+struct AggregationLimits {
+    /// Memory, in bytes.
+    memory_limit: Option<u64>,
+    bucket_limit: Option<u32>,
 }
 
 #[derive(Serialize)]
@@ -54,87 +176,604 @@ struct Serp {
     q: String,
     num_hits: usize,
     hits: Vec<Hit>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    aggregations: Option<AggregationResults>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    facets: FacetCountsByPath,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    query_errors: Vec<String>,
+    timings: TimerTree,
+}
+
+#[derive(Serialize)]
+struct CountResponse {
+    q: String,
+    count: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    query_errors: Vec<String>,
+    timings: TimerTree,
+}
+
+/// Body of the response to a request that failed.
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    query_errors: Vec<String>,
+}
+
+/// Why a call to the API failed.
+#[derive(Debug)]
+enum ApiError {
+    /// The request is missing a parameter, or one of them is invalid.
+    BadRequest(String),
+    /// The query could not be parsed, and the request was not lenient.
+    InvalidQuery(QueryParserError),
+    Tantivy(TantivyError),
+}
+
+impl ApiError {
+    /// Status and body of the response to the failed request.
+    fn response(self) -> (status::Status, ErrorResponse) {
+        match self {
+            ApiError::BadRequest(error) => (
+                status::BadRequest,
+                ErrorResponse {
+                    error,
+                    query_errors: Vec::new(),
+                },
+            ),
+            ApiError::InvalidQuery(query_error) => (
+                status::BadRequest,
+                ErrorResponse {
+                    error: "Invalid query".to_string(),
+                    query_errors: vec![query_error.to_string()],
+                },
+            ),
+            ApiError::Tantivy(error) => {
+                let status = match error {
+                    TantivyError::InvalidArgument(_)
+                    | TantivyError::FieldNotFound(_)
+                    | TantivyError::SchemaError(_)
+                    | TantivyError::AggregationError(_) => status::BadRequest,
+                    _ => status::InternalServerError,
+                };
+                (
+                    status,
+                    ErrorResponse {
+                        error: error.to_string(),
+                        query_errors: Vec::new(),
+                    },
+                )
+            }
+        }
+    }
+}
+
+impl From<TantivyError> for ApiError {
+    fn from(error: TantivyError) -> ApiError {
+        ApiError::Tantivy(error)
+    }
+}
+
+impl From<ApiError> for IronError {
+    fn from(error: ApiError) -> IronError {
+        let (status, response) = error.response();
+        let resp_json = serde_json::to_string_pretty(&response).unwrap();
+        let content_type = "application/json".parse::<Mime>().unwrap();
+        IronError::new(
+            StringError(response.error),
+            (content_type, status, resp_json),
+        )
+    }
+}
+
+fn bad_request(error: &str) -> ApiError {
+    ApiError::BadRequest(error.to_string())
+}
+
+#[derive(Serialize)]
+struct Suggestions {
+    q: String,
+    field: String,
+    suggestions: Vec<String>,
     timings: TimerTree,
 }
 
 #[derive(Serialize)]
 struct Hit {
-    score: Score,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    index: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    score: Option<Score>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sort_value: Option<OwnedValue>,
     doc: NamedFieldDocument,
+    /// Address of the document, to ask for the documents like it to /api/mlt.
+    doc_address: DocAddress,
     id: u32,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    snippets: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    explanation: Option<Explanation>,
 }
 
-struct IndexServer {
+/// Parameters of a call to `/api`.
+struct SearchRequest {
+    q: String,
+    num_hits: usize,
+    offset: usize,
+    sort_by: Option<SortBy>,
+    snippet_fields: Vec<String>,
+    explain: bool,
+    lenient: bool,
+    /// Fields to return, all the stored fields if `None`.
+    fields: Option<Vec<String>>,
+    /// Query settings overriding the ones of the server for this request.
+    query_settings: QuerySettings,
+    /// Aggregations run alongside the search.
+    aggregations: Option<Aggregations>,
+    /// Facet paths whose children are counted.
+    facets: Vec<String>,
+    /// More-like-this query run instead of `q`.
+    like: Option<LikeQuery>,
+}
+
+/// Body of a POST request to `/api` or `/api/aggregate`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SearchBody {
+    q: String,
+    nhits: Option<usize>,
+    #[serde(default)]
+    offset: usize,
+    sort: Option<String>,
+    #[serde(default)]
+    snippet: Vec<String>,
+    #[serde(default)]
+    explain: bool,
+    conjunction: Option<bool>,
+    #[serde(default)]
+    fuzzy: Vec<String>,
+    fields: Option<Vec<String>>,
+    #[serde(default)]
+    lenient: bool,
+    aggs: Option<serde_json::Value>,
+    #[serde(default)]
+    facet: Vec<String>,
+}
+
+/// Largest `nhits` and `offset` of a request. The collectors allocate room
+/// for `offset + nhits` documents upfront.
+const MAX_HITS: usize = 10_000;
+
+/// Most suggestions a call to /api/autocomplete may return.
+const MAX_SUGGESTIONS: usize = 100;
+
+/// Documents read by /api/autocomplete per requested suggestion, to find
+/// distinct values among documents sharing the same one.
+const AUTOCOMPLETE_DOCS_PER_SUGGESTION: usize = 10;
+
+/// An index of the server, with its own query settings.
+struct ServedIndex {
+    /// Name of the index in the hits, when the server has several indexes.
+    name: String,
+    index: Index,
     reader: IndexReader,
+    query_settings: QuerySettings,
     query_parser: QueryParser,
-    schema: Schema,
 }
 
-impl IndexServer {
-    fn load(path: &Path) -> tantivy::Result<IndexServer> {
-        let index = Index::open_in_dir(path)?;
-        let schema = index.schema();
-        let default_fields: Vec<Field> = schema
-            .fields()
-            .filter(|&(_, field_entry)| match field_entry.field_type() {
-                FieldType::Str(ref text_field_options) => {
-                    text_field_options.get_indexing_options().is_some()
-                }
-                _ => false,
-            })
-            .map(|(field, _)| field)
-            .collect();
-        let query_parser =
-            QueryParser::new(schema.clone(), default_fields, index.tokenizers().clone());
+impl ServedIndex {
+    fn new(
+        name: String,
+        index: Index,
+        query_settings: QuerySettings,
+    ) -> tantivy::Result<ServedIndex> {
+        let query_parser = query_settings.query_parser(&index)?;
         let reader = index.reader()?;
-        Ok(IndexServer {
+        Ok(ServedIndex {
+            name,
+            index,
             reader,
+            query_settings,
             query_parser,
-            schema,
         })
     }
 
-    fn create_hit<D: Document>(&self, score: Score, doc: D, doc_address: DocAddress) -> Hit {
+    /// Parses `q` with the query settings of the index overridden by
+    /// `query_settings`. Lenient parsing also returns the parsing errors.
+    fn parse_query(
+        &self,
+        q: &str,
+        lenient: bool,
+        query_settings: QuerySettings,
+    ) -> Result<(Box<dyn Query>, Vec<QueryParserError>), ApiError> {
+        let request_query_parser;
+        let query_parser = if query_settings == QuerySettings::default() {
+            &self.query_parser
+        } else {
+            request_query_parser = self
+                .query_settings
+                .clone()
+                .overridden_by(query_settings)
+                .query_parser(&self.index)?;
+            &request_query_parser
+        };
+        if lenient {
+            Ok(query_parser.parse_query_lenient(q))
+        } else {
+            let query = query_parser
+                .parse_query(q)
+                .map_err(ApiError::InvalidQuery)?;
+            Ok((query, Vec::new()))
+        }
+    }
+}
+
+/// The query of a request, parsed for one of the indexes of the server.
+struct IndexQuery {
+    searcher: Searcher,
+    query: Box<dyn Query>,
+}
+
+struct IndexServer {
+    /// Indexes sharing the same schema, searched together.
+    indexes: Vec<ServedIndex>,
+    schema: Schema,
+    aggregation_limits: AggregationLimits,
+}
+
+impl IndexServer {
+    /// Opens the indexes of `directories`, which must have the same schema.
+    /// The query settings stored with each index are overridden by
+    /// `query_settings`.
+    fn load(
+        directories: &[PathBuf],
+        query_settings: QuerySettings,
+        aggregation_limits: AggregationLimits,
+    ) -> tantivy::Result<IndexServer> {
+        let mut indexes = Vec::new();
+        for named_index in open_indexes(directories)? {
+            let query_settings =
+                load_query_settings(&named_index.directory)?.overridden_by(query_settings.clone());
+            indexes.push(ServedIndex::new(
+                named_index.name,
+                named_index.index,
+                query_settings,
+            )?);
+        }
+        Ok(IndexServer::new(indexes, aggregation_limits))
+    }
+
+    fn new(indexes: Vec<ServedIndex>, aggregation_limits: AggregationLimits) -> IndexServer {
+        IndexServer {
+            schema: indexes[0].index.schema(),
+            indexes,
+            aggregation_limits,
+        }
+    }
+
+    /// Aggregation context of a request. The limits are tracked per request.
+    fn agg_context_params(&self) -> AggContextParams {
+        AggContextParams::new(
+            AggregationLimitsGuard::new(
+                self.aggregation_limits.memory_limit,
+                self.aggregation_limits.bucket_limit,
+            ),
+            self.indexes[0].index.tokenizers().clone(),
+        )
+    }
+
+    fn create_hit(
+        &self,
+        score: Option<Score>,
+        sort_value: Option<OwnedValue>,
+        doc: NamedFieldDocument,
+        doc_address: DocAddress,
+        snippets: BTreeMap<String, String>,
+        explanation: Option<Explanation>,
+    ) -> Hit {
         Hit {
+            index: None,
             score,
-            doc: doc.to_named_doc(&self.schema),
+            sort_value,
+            doc,
+            doc_address,
             id: doc_address.doc_id,
+            snippets,
+            explanation,
         }
     }
 
-    fn search(&self, q: String, num_hits: usize, offset: usize) -> tantivy::Result<Serp> {
-        let query = self
-            .query_parser
-            .parse_query(&q)
-            .expect("Parsing the query failed");
-        let searcher = self.reader.searcher();
+    /// Parses `q` for each index, returning the parsing errors of lenient
+    /// parsing without duplicates.
+    fn parse_query(
+        &self,
+        q: &str,
+        lenient: bool,
+        query_settings: QuerySettings,
+    ) -> Result<(Vec<IndexQuery>, Vec<String>), ApiError> {
+        let mut index_queries = Vec::new();
+        let mut query_errors: Vec<String> = Vec::new();
+        for served_index in &self.indexes {
+            let (query, errors) = served_index.parse_query(q, lenient, query_settings.clone())?;
+            for error in errors {
+                let error = error.to_string();
+                if !query_errors.contains(&error) {
+                    query_errors.push(error);
+                }
+            }
+            index_queries.push(IndexQuery {
+                searcher: served_index.reader.searcher(),
+                query,
+            });
+        }
+        Ok((index_queries, query_errors))
+    }
+
+    /// Builds a more-like-this query for each index. Its terms are drawn from
+    /// the first index, where a liked document is read.
+    fn like_queries(&self, like_query: &LikeQuery) -> tantivy::Result<Vec<IndexQuery>> {
+        let query = like_query.query(&self.indexes[0].reader.searcher())?;
+        Ok(self
+            .indexes
+            .iter()
+            .map(|served_index| IndexQuery {
+                searcher: served_index.reader.searcher(),
+                query: Box::new(query.clone()),
+            })
+            .collect())
+    }
+
+    fn count(
+        &self,
+        q: String,
+        lenient: bool,
+        query_settings: QuerySettings,
+    ) -> Result<CountResponse, ApiError> {
+        let (index_queries, query_errors) = self.parse_query(&q, lenient, query_settings)?;
         let mut timer_tree = TimerTree::default();
-        let (top_docs, num_hits) = {
+        let mut count = 0;
+        {
+            let _count_timer = timer_tree.open("count");
+            for IndexQuery { searcher, query } in &index_queries {
+                count += searcher.search(query, &Count)?;
+            }
+        }
+        Ok(CountResponse {
+            q,
+            count,
+            query_errors,
+            timings: timer_tree,
+        })
+    }
+
+    fn search(&self, request: SearchRequest) -> Result<Serp, ApiError> {
+        let SearchRequest {
+            q,
+            num_hits,
+            offset,
+            sort_by,
+            snippet_fields,
+            explain,
+            lenient,
+            fields,
+            query_settings,
+            aggregations,
+            facets,
+            like,
+        } = request;
+        if num_hits > MAX_HITS || offset > MAX_HITS {
+            return Err(bad_request(&format!(
+                "Parameters nhits and offset must be at most {}",
+                MAX_HITS
+            )));
+        }
+        let projection = match fields {
+            Some(fields) => FieldProjection::new(&self.schema, &fields)?,
+            None => FieldProjection::all_stored(),
+        };
+        let (index_queries, query_errors) = match &like {
+            Some(like_query) => (self.like_queries(like_query)?, Vec::new()),
+            None => self.parse_query(&q, lenient, query_settings)?,
+        };
+        let snippets_per_index = index_queries
+            .iter()
+            .map(|IndexQuery { searcher, query }| {
+                Snippets::create(searcher, query, &snippet_fields, SnippetFormat::Html)
+            })
+            .collect::<tantivy::Result<Vec<_>>>()?;
+        let facet_paths = FacetPaths::parse(&self.schema, &facets)?;
+        let agg_context = self.agg_context_params();
+        let mut timer_tree = TimerTree::default();
+        let mut count = 0;
+        let mut top_docs_per_index = Vec::new();
+        let mut intermediate_aggregations: Option<IntermediateAggregationResults> = None;
+        let mut facet_counts = FacetCountsByPath::new();
+        {
             let _search_timer = timer_tree.open("search");
-            searcher.search(
-                &query,
-                &(
-                    TopDocs::with_limit(num_hits)
-                        .and_offset(offset)
-                        .order_by_score(),
-                    Count,
-                ),
-            )?
+            for (index_ord, IndexQuery { searcher, query }) in index_queries.iter().enumerate() {
+                // The liked document is read from the first index.
+                let like_query = like.as_ref().filter(|_| index_ord == 0);
+                let aggregation_collector = aggregations.as_ref().map(|aggregations| {
+                    DistributedAggregationCollector::from_aggs(
+                        aggregations.clone(),
+                        agg_context.clone(),
+                    )
+                });
+                let (facet_collector, facet_handles) = facet_paths.collector();
+                // The aggregations and the facets are collected in the same pass
+                // as the hits.
+                let collectors = (Count, aggregation_collector, facet_collector);
+                // Each index returns its `offset + num_hits` best documents,
+                // which are then merged, and one more in place of the liked
+                // document.
+                let (mut top_docs, (mut index_count, index_aggregations, facet_fruits)) =
+                    if num_hits == 0 {
+                        (Vec::new(), searcher.search(query, &collectors)?)
+                    } else {
+                        search_top_docs(
+                            searcher,
+                            query,
+                            sort_by.as_ref(),
+                            offset + num_hits + usize::from(like_query.is_some()),
+                            0,
+                            collectors,
+                        )?
+                    };
+                if like_query.is_some_and(|like_query| like_query.remove_liked_doc(&mut top_docs)) {
+                    index_count -= 1;
+                }
+                count += index_count;
+                top_docs_per_index.push(top_docs);
+                merge_facet_counts(&mut facet_counts, facet_handles.counts(facet_fruits));
+                if let Some(index_aggregations) = index_aggregations {
+                    match &mut intermediate_aggregations {
+                        Some(merged) => merged.merge_fruits(index_aggregations)?,
+                        None => intermediate_aggregations = Some(index_aggregations),
+                    }
+                }
+            }
+        }
+        let aggregations = match (aggregations, intermediate_aggregations) {
+            (Some(aggregations), Some(intermediate_aggregations)) => Some(
+                intermediate_aggregations.into_final_result(aggregations, agg_context.limits)?,
+            ),
+            _ => None,
         };
+        let top_docs = merge_top_docs(top_docs_per_index, sort_by.as_ref(), num_hits, offset);
         let hits: Vec<Hit> = {
             let _fetching_timer = timer_tree.open("fetching docs");
             top_docs
-                .iter()
-                .map(|(score, doc_address)| {
-                    let doc = searcher.doc::<TantivyDocument>(*doc_address).unwrap();
-                    self.create_hit(*score, doc, *doc_address)
+                .into_iter()
+                .map(|(index_ord, (score, sort_value, doc_address))| {
+                    let IndexQuery { searcher, query } = &index_queries[index_ord];
+                    let snippets = &snippets_per_index[index_ord];
+                    let doc: Option<TantivyDocument> =
+                        if projection.needs_stored_doc() || !snippets.is_empty() {
+                            Some(searcher.doc(doc_address)?)
+                        } else {
+                            None
+                        };
+                    let snippets = doc
+                        .as_ref()
+                        .map(|doc| snippets.render(doc))
+                        .unwrap_or_default();
+                    let named_doc = projection.named_doc(searcher, doc_address, doc.as_ref())?;
+                    let explanation = if explain {
+                        Some(query.explain(searcher, doc_address)?)
+                    } else {
+                        None
+                    };
+                    let hit = self.create_hit(
+                        score,
+                        sort_value,
+                        named_doc,
+                        doc_address,
+                        snippets,
+                        explanation,
+                    );
+                    // Hits are only tagged with the name of their index if there
+                    // are several.
+                    Ok(Hit {
+                        index: (self.indexes.len() > 1)
+                            .then(|| self.indexes[index_ord].name.clone()),
+                        ..hit
+                    })
                 })
-                .collect()
+                .collect::<tantivy::Result<_>>()?
         };
         Ok(Serp {
             q,
-            num_hits,
+            num_hits: count,
             hits,
+            aggregations,
+            facets: facet_counts,
+            query_errors,
+            timings: timer_tree,
+        })
+    }
+
+    fn autocomplete(
+        &self,
+        q: String,
+        field_name: String,
+        num_suggestions: usize,
+    ) -> tantivy::Result<Suggestions> {
+        let field = self.schema.get_field(&field_name)?;
+        let field_entry = self.schema.get_field_entry(field);
+        if !matches!(field_entry.field_type(), FieldType::Str(_)) || !field_entry.is_stored() {
+            return Err(TantivyError::InvalidArgument(format!(
+                "Field `{}` must be a stored text field to be autocompleted",
+                field_name
+            )));
+        }
+        if num_suggestions > MAX_SUGGESTIONS {
+            return Err(TantivyError::InvalidArgument(format!(
+                "At most {} suggestions can be requested",
+                MAX_SUGGESTIONS
+            )));
+        }
+        let mut timer_tree = TimerTree::default();
+        let mut suggestions: Vec<String> = Vec::new();
+        // Number of documents the request may still read.
+        let mut docs_budget = num_suggestions * AUTOCOMPLETE_DOCS_PER_SUGGESTION;
+        {
+            let _autocomplete_timer = timer_tree.open("autocomplete");
+            // The indexes are completed in turn, until there are enough suggestions.
+            for served_index in &self.indexes {
+                if suggestions.len() >= num_suggestions {
+                    break;
+                }
+                let mut text_analyzer = served_index.index.tokenizer_for_field(field)?;
+                let mut terms: Vec<Term> = Vec::new();
+                text_analyzer
+                    .token_stream(&q)
+                    .process(&mut |token| terms.push(Term::from_field_text(field, &token.text)));
+                terms.sort();
+                terms.dedup();
+                if terms.is_empty() {
+                    continue;
+                }
+                let query = BooleanQuery::new(
+                    terms
+                        .into_iter()
+                        .map(|term| {
+                            let term_query: Box<dyn Query> =
+                                Box::new(TermQuery::new(term, IndexRecordOption::WithFreqs));
+                            (Occur::Must, term_query)
+                        })
+                        .collect(),
+                );
+                let searcher = served_index.reader.searcher();
+                // Several documents may share the same value, so more documents
+                // than suggestions are read, in a single search per index, until
+                // the budget of the request is spent.
+                if docs_budget == 0 {
+                    break;
+                }
+                let top_docs =
+                    searcher.search(&query, &TopDocs::with_limit(docs_budget).order_by_score())?;
+                docs_budget -= top_docs.len();
+                for (_score, doc_address) in top_docs {
+                    let doc: TantivyDocument = searcher.doc(doc_address)?;
+                    let Some(value) = doc.get_first(field).and_then(|value| value.as_str()) else {
+                        continue;
+                    };
+                    if !suggestions.iter().any(|suggestion| suggestion == value) {
+                        suggestions.push(value.to_string());
+                        if suggestions.len() >= num_suggestions {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(Suggestions {
+            q,
+            field: field_name,
+            suggestions,
             timings: timer_tree,
         })
     }
@@ -161,45 +800,280 @@ impl Error for StringError {
 
 fn search(req: &mut Request<'_, '_>) -> IronResult<Response> {
     let index_server = req.get::<Read<IndexServer>>().unwrap();
+    let serp = if req.method == Method::Post {
+        search_with_body(&index_server, &read_body(req)?)?
+    } else {
+        search_with_params(&index_server, query_map(req)?)?
+    };
+    json_response(&serp)
+}
+
+fn aggregate(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    let index_server = req.get::<Read<IndexServer>>().unwrap();
+    if req.method != Method::Post {
+        return Err(bad_request("Aggregations must be sent in a POST request").into());
+    }
+    json_response(&aggregate_with_body(&index_server, &read_body(req)?)?)
+}
+
+fn count(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    let index_server = req.get::<Read<IndexServer>>().unwrap();
+    json_response(&count_with_params(&index_server, query_map(req)?)?)
+}
+
+fn more_like_this(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    let index_server = req.get::<Read<IndexServer>>().unwrap();
+    json_response(&more_like_this_with_params(&index_server, query_map(req)?)?)
+}
+
+fn autocomplete(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    let index_server = req.get::<Read<IndexServer>>().unwrap();
+    json_response(&autocomplete_with_params(&index_server, query_map(req)?)?)
+}
+
+/// Reads the arguments of a GET request.
+fn query_map<'a>(req: &'a mut Request<'_, '_>) -> IronResult<&'a QueryMap> {
     req.get_ref::<UrlEncodedQuery>()
-        .map_err(|_| {
-            IronError::new(
-                StringError(String::from("Failed to decode error")),
-                status::BadRequest,
-            )
-        })
-        .and_then(|qs_map| {
-            let num_hits: usize = qs_map
-                .get("nhits")
-                .and_then(|nhits_str| usize::from_str(&nhits_str[0]).ok())
-                .unwrap_or(10);
-            let query = qs_map.get("q").ok_or_else(|| {
-                IronError::new(
-                    StringError(String::from("Parameter q is missing from the query")),
-                    status::BadRequest,
-                )
-            })?[0]
-                .clone();
-            let offset: usize = qs_map
-                .get("offset")
-                .and_then(|offset_str| usize::from_str(&offset_str[0]).ok())
-                .unwrap_or(0);
-            let serp = index_server.search(query, num_hits, offset).unwrap();
-            let resp_json = serde_json::to_string_pretty(&serp).unwrap();
-            let content_type = "application/json".parse::<Mime>().unwrap();
-            Ok(Response::with((
-                content_type,
-                status::Ok,
-                resp_json.to_string(),
-            )))
-        })
+        .map_err(|_| bad_request("Failed to decode error").into())
+}
+
+/// Reads the body of a POST request.
+fn read_body(req: &mut Request<'_, '_>) -> IronResult<String> {
+    let mut body = String::new();
+    req.body
+        .read_to_string(&mut body)
+        .map_err(|_| bad_request("Failed to read the request body"))?;
+    Ok(body)
+}
+
+fn json_response<T: serde::Serialize>(response: &T) -> IronResult<Response> {
+    let resp_json = serde_json::to_string_pretty(response).unwrap();
+    let content_type = "application/json".parse::<Mime>().unwrap();
+    Ok(Response::with((content_type, status::Ok, resp_json)))
+}
+
+/// First value of the argument `name`.
+fn param<'a>(qs_map: &'a QueryMap, name: &str) -> Option<&'a str> {
+    qs_map
+        .get(name)
+        .and_then(|values| values.first())
+        .map(String::as_str)
+}
+
+fn required_param(qs_map: &QueryMap, name: &str) -> Result<String, ApiError> {
+    param(qs_map, name)
+        .map(str::to_string)
+        .ok_or_else(|| bad_request(&format!("Parameter {} is missing from the query", name)))
+}
+
+/// Value of a numeric argument, `None` if it is missing or invalid.
+fn numeric_param<T: FromStr>(qs_map: &QueryMap, name: &str) -> Option<T> {
+    param(qs_map, name).and_then(|value| T::from_str(value).ok())
+}
+
+fn flag_param(qs_map: &QueryMap, name: &str) -> bool {
+    param(qs_map, name) == Some("true")
+}
+
+/// Value of a `true` or `false` argument, `None` if it is missing or
+/// invalid.
+fn optional_flag_param(qs_map: &QueryMap, name: &str) -> Option<bool> {
+    param(qs_map, name).and_then(|value| value.parse().ok())
+}
+
+/// Values of an argument holding a list separated by commas, which may be
+/// repeated.
+fn list_param(qs_map: &QueryMap, name: &str) -> Vec<String> {
+    qs_map
+        .get(name)
+        .into_iter()
+        .flatten()
+        .flat_map(|values| values.split(','))
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
+/// Query settings set by the `conjunction` and `fuzzy` arguments.
+fn request_query_settings(qs_map: &QueryMap) -> Result<QuerySettings, ApiError> {
+    let fuzzy = list_param(qs_map, "fuzzy")
+        .iter()
+        .map(|fuzzy| parse_fuzzy(fuzzy))
+        .collect::<Result<_, _>>()
+        .map_err(|e| bad_request(&e))?;
+    Ok(QuerySettings {
+        conjunction_by_default: optional_flag_param(qs_map, "conjunction"),
+        fuzzy,
+        ..QuerySettings::default()
+    })
+}
+
+/// GET /api
+fn search_with_params(index_server: &IndexServer, qs_map: &QueryMap) -> Result<Serp, ApiError> {
+    let num_hits: usize = numeric_param(qs_map, "nhits").unwrap_or(10);
+    if num_hits == 0 {
+        return Err(bad_request("Parameter nhits must be greater than 0"));
+    }
+    let sort_by = param(qs_map, "sort")
+        .map(|sort| sort.parse::<SortBy>())
+        .transpose()
+        .map_err(|e| bad_request(&e))?;
+    index_server.search(SearchRequest {
+        q: required_param(qs_map, "q")?,
+        num_hits,
+        offset: numeric_param(qs_map, "offset").unwrap_or(0),
+        sort_by,
+        snippet_fields: list_param(qs_map, "snippet"),
+        explain: flag_param(qs_map, "explain"),
+        lenient: flag_param(qs_map, "lenient"),
+        fields: qs_map
+            .contains_key("fields")
+            .then(|| list_param(qs_map, "fields")),
+        query_settings: request_query_settings(qs_map)?,
+        aggregations: None,
+        facets: list_param(qs_map, "facet"),
+        like: None,
+    })
+}
+
+/// POST /api
+fn search_with_body(index_server: &IndexServer, body: &str) -> Result<Serp, ApiError> {
+    index_server.search(search_request(parse_search_body(body)?, 10)?)
+}
+
+/// POST /api/aggregate
+fn aggregate_with_body(index_server: &IndexServer, body: &str) -> Result<Serp, ApiError> {
+    let body = parse_search_body(body)?;
+    if body.aggs.is_none() {
+        return Err(bad_request("Parameter aggs is missing from the request"));
+    }
+    index_server.search(search_request(body, 0)?)
+}
+
+/// Parses the search request sent as JSON in the body of a POST request.
+fn parse_search_body(body: &str) -> Result<SearchBody, ApiError> {
+    serde_json::from_str(body).map_err(|e| bad_request(&format!("Invalid request body: {}", e)))
+}
+
+fn search_request(body: SearchBody, default_num_hits: usize) -> Result<SearchRequest, ApiError> {
+    let num_hits = body.nhits.unwrap_or(default_num_hits);
+    if num_hits == 0 && body.aggs.is_none() && body.facet.is_empty() {
+        return Err(bad_request("Parameter nhits must be greater than 0"));
+    }
+    let sort_by = body
+        .sort
+        .map(|sort| sort.parse::<SortBy>())
+        .transpose()
+        .map_err(|e| bad_request(&e))?;
+    let fuzzy = body
+        .fuzzy
+        .iter()
+        .map(|fuzzy| parse_fuzzy(fuzzy))
+        .collect::<Result<_, _>>()
+        .map_err(|e| bad_request(&e))?;
+    let aggregations = body.aggs.map(aggregations_from_value).transpose()?;
+    Ok(SearchRequest {
+        q: body.q,
+        num_hits,
+        offset: body.offset,
+        sort_by,
+        snippet_fields: body.snippet,
+        explain: body.explain,
+        lenient: body.lenient,
+        fields: body.fields,
+        query_settings: QuerySettings {
+            conjunction_by_default: body.conjunction,
+            fuzzy,
+            ..QuerySettings::default()
+        },
+        aggregations,
+        facets: body.facet,
+        like: None,
+    })
+}
+
+/// GET /api/count
+fn count_with_params(
+    index_server: &IndexServer,
+    qs_map: &QueryMap,
+) -> Result<CountResponse, ApiError> {
+    index_server.count(
+        required_param(qs_map, "q")?,
+        flag_param(qs_map, "lenient"),
+        request_query_settings(qs_map)?,
+    )
+}
+
+/// GET /api/mlt
+fn more_like_this_with_params(
+    index_server: &IndexServer,
+    qs_map: &QueryMap,
+) -> Result<Serp, ApiError> {
+    let (q, target) = match (param(qs_map, "doc"), param(qs_map, "text")) {
+        (Some(doc_address), None) => (
+            doc_address.to_string(),
+            LikeTarget::Doc(parse_doc_address(doc_address).map_err(|e| bad_request(&e))?),
+        ),
+        (None, Some(text)) => (text.to_string(), LikeTarget::Text(text.to_string())),
+        _ => {
+            return Err(bad_request(
+                "Exactly one of the parameters doc and text is required",
+            ))
+        }
+    };
+    let num_hits: usize = numeric_param(qs_map, "nhits").unwrap_or(10);
+    if num_hits == 0 {
+        return Err(bad_request("Parameter nhits must be greater than 0"));
+    }
+    index_server.search(SearchRequest {
+        q,
+        num_hits,
+        offset: numeric_param(qs_map, "offset").unwrap_or(0),
+        sort_by: None,
+        snippet_fields: Vec::new(),
+        explain: flag_param(qs_map, "explain"),
+        lenient: false,
+        fields: qs_map
+            .contains_key("fields")
+            .then(|| list_param(qs_map, "fields")),
+        query_settings: QuerySettings::default(),
+        aggregations: None,
+        facets: Vec::new(),
+        like: Some(LikeQuery {
+            target,
+            fields: list_param(qs_map, "like_fields"),
+            min_doc_frequency: numeric_param(qs_map, "min_doc_freq"),
+            min_term_frequency: numeric_param(qs_map, "min_term_freq"),
+            max_query_terms: numeric_param(qs_map, "max_query_terms"),
+        }),
+    })
+}
+
+/// GET /api/autocomplete
+fn autocomplete_with_params(
+    index_server: &IndexServer,
+    qs_map: &QueryMap,
+) -> Result<Suggestions, ApiError> {
+    let num_suggestions: usize = numeric_param(qs_map, "n").unwrap_or(10);
+    let q = required_param(qs_map, "q")?;
+    let field = required_param(qs_map, "field")?;
+    Ok(index_server.autocomplete(q, field, num_suggestions)?)
 }
 
-fn run_serve(directory: PathBuf, host: &str) -> tantivy::Result<()> {
+fn run_serve(
+    directories: &[PathBuf],
+    host: &str,
+    query_settings: QuerySettings,
+    aggregation_limits: AggregationLimits,
+) -> tantivy::Result<()> {
     let mut mount = Mount::new();
-    let server = IndexServer::load(&directory)?;
+    let server = IndexServer::load(directories, query_settings, aggregation_limits)?;
 
     mount.mount("/api", search);
+    mount.mount("/api/aggregate", aggregate);
+    mount.mount("/api/count", count);
+    mount.mount("/api/mlt", more_like_this);
+    mount.mount("/api/autocomplete", autocomplete);
 
     let mut middleware = Chain::new(mount);
     middleware.link(Read::<IndexServer>::both(server));
@@ -208,3 +1082,360 @@ fn run_serve(directory: PathBuf, host: &str) -> tantivy::Result<()> {
     Iron::new(middleware).http(host).unwrap();
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_index::create_index;
+    use serde_json::json;
+    use tantivy::doc;
+    use tantivy::schema::{FAST, STORED, STRING, TEXT};
+
+    fn test_server(
+        query_settings: QuerySettings,
+        aggregation_limits: AggregationLimits,
+    ) -> IndexServer {
+        let mut schema_builder = Schema::builder();
+        let title = schema_builder.add_text_field("title", TEXT | STORED);
+        let lang = schema_builder.add_text_field("lang", STRING | FAST);
+        let docs = [("paris city", "fr"), ("paris hotel", "en"), ("lyon", "fr")]
+            .map(|(title_text, lang_code)| doc!(title => title_text, lang => lang_code));
+        let index = create_index(schema_builder.build(), docs);
+        index_server(index, query_settings, aggregation_limits)
+    }
+
+    fn index_server(
+        index: Index,
+        query_settings: QuerySettings,
+        aggregation_limits: AggregationLimits,
+    ) -> IndexServer {
+        let served_index = ServedIndex::new("test".to_string(), index, query_settings).unwrap();
+        IndexServer::new(vec![served_index], aggregation_limits)
+    }
+
+    fn default_server() -> IndexServer {
+        test_server(QuerySettings::default(), unlimited_aggregations())
+    }
+
+    fn unlimited_aggregations() -> AggregationLimits {
+        AggregationLimits {
+            memory_limit: None,
+            bucket_limit: None,
+        }
+    }
+
+    fn params(pairs: &[(&str, &str)]) -> QueryMap {
+        let mut qs_map = QueryMap::new();
+        for (name, value) in pairs {
+            qs_map
+                .entry(name.to_string())
+                .or_default()
+                .push(value.to_string());
+        }
+        qs_map
+    }
+
+    /// Status and JSON body of the response to a failed request.
+    fn error_response<T>(result: Result<T, ApiError>) -> (status::Status, serde_json::Value) {
+        let Err(error) = result else {
+            panic!("the request should have failed");
+        };
+        let (status, response) = error.response();
+        (status, serde_json::to_value(response).unwrap())
+    }
+
+    #[test]
+    fn invalid_queries_are_bad_requests() {
+        let server = default_server();
+        let (status, body) =
+            error_response(search_with_params(&server, &params(&[("q", "title:(")])));
+        assert_eq!(status, status::BadRequest);
+        assert_eq!(body["error"], "Invalid query");
+        assert_eq!(body["query_errors"].as_array().unwrap().len(), 1);
+
+        let (status, body) = error_response(search_with_params(&server, &params(&[])));
+        assert_eq!(status, status::BadRequest);
+        assert_eq!(
+            body,
+            json!({"error": "Parameter q is missing from the query"})
+        );
+
+        let (status, _) = error_response(search_with_params(
+            &server,
+            &params(&[("q", "paris"), ("fields", "missing")]),
+        ));
+        assert_eq!(status, status::BadRequest);
+    }
+
+    #[test]
+    fn too_many_hits_are_bad_requests() {
+        let server = default_server();
+        for (name, value) in [
+            ("offset", "18446744073709551615"),
+            ("nhits", "100000000000"),
+        ] {
+            let (status, _) = error_response(search_with_params(
+                &server,
+                &params(&[("q", "paris"), (name, value)]),
+            ));
+            assert_eq!(status, status::BadRequest);
+        }
+        let (status, _) = error_response(search_with_body(
+            &server,
+            r#"{"q": "paris", "offset": 18446744073709551615}"#,
+        ));
+        assert_eq!(status, status::BadRequest);
+    }
+
+    #[test]
+    fn lenient_queries_report_their_errors() {
+        let server = default_server();
+        let serp = search_with_params(
+            &server,
+            &params(&[("q", "paris title:("), ("lenient", "true")]),
+        )
+        .unwrap();
+        assert_eq!(serp.num_hits, 2);
+        assert!(!serp.query_errors.is_empty());
+    }
+
+    #[test]
+    fn count_matching_documents() {
+        let server = default_server();
+        let count = count_with_params(&server, &params(&[("q", "paris")])).unwrap();
+        assert_eq!(count.count, 2);
+        let count = count_with_params(
+            &server,
+            &params(&[("q", "paris lyon"), ("conjunction", "true")]),
+        )
+        .unwrap();
+        assert_eq!(count.count, 0);
+
+        let (status, body) =
+            error_response(count_with_params(&server, &params(&[("q", "title:(")])));
+        assert_eq!(status, status::BadRequest);
+        assert_eq!(body["error"], "Invalid query");
+        let count = count_with_params(
+            &server,
+            &params(&[("q", "paris title:("), ("lenient", "true")]),
+        )
+        .unwrap();
+        assert_eq!(count.count, 2);
+        assert!(!count.query_errors.is_empty());
+    }
+
+    #[test]
+    fn requests_override_the_index_conjunction() {
+        let server = test_server(
+            QuerySettings {
+                conjunction_by_default: Some(true),
+                ..QuerySettings::default()
+            },
+            unlimited_aggregations(),
+        );
+        let count = |conjunction: Option<&str>| {
+            let mut pairs = vec![("q", "paris lyon")];
+            pairs.extend(conjunction.map(|conjunction| ("conjunction", conjunction)));
+            count_with_params(&server, &params(&pairs)).unwrap().count
+        };
+        assert_eq!(count(None), 0);
+        assert_eq!(count(Some("false")), 3);
+        assert_eq!(count(Some("true")), 0);
+
+        let serp =
+            search_with_body(&server, r#"{"q": "paris lyon", "conjunction": false}"#).unwrap();
+        assert_eq!(serp.num_hits, 3);
+    }
+
+    #[test]
+    fn post_search_with_aggregations() {
+        let server = default_server();
+        let serp = search_with_body(
+            &server,
+            r#"{"q": "paris", "nhits": 1, "fields": ["title"],
+                "aggs": {"langs": {"terms": {"field": "lang"}}}}"#,
+        )
+        .unwrap();
+        let serp = serde_json::to_value(serp).unwrap();
+        assert_eq!(serp["num_hits"], 2);
+        assert_eq!(serp["hits"].as_array().unwrap().len(), 1);
+        assert_eq!(
+            serp["aggregations"]["langs"]["buckets"]
+                .as_array()
+                .unwrap()
+                .len(),
+            2
+        );
+
+        let (status, body) =
+            error_response(search_with_body(&server, r#"{"q": "paris", "nhits": 0}"#));
+        assert_eq!(status, status::BadRequest);
+        assert_eq!(body["error"], "Parameter nhits must be greater than 0");
+        let (status, body) = error_response(search_with_body(&server, r#"{"query": "paris"}"#));
+        assert_eq!(status, status::BadRequest);
+        assert!(body["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("Invalid request body"));
+    }
+
+    #[test]
+    fn aggregate_endpoint() {
+        let server = default_server();
+        let serp = aggregate_with_body(
+            &server,
+            r#"{"q": "*", "aggs": {"langs": {"terms": {"field": "lang"}}}}"#,
+        )
+        .unwrap();
+        let serp = serde_json::to_value(serp).unwrap();
+        assert_eq!(serp["num_hits"], 3);
+        assert_eq!(serp["hits"], json!([]));
+        assert_eq!(
+            serp["aggregations"]["langs"]["buckets"][0],
+            json!({"key": "fr", "doc_count": 2})
+        );
+
+        let (status, body) = error_response(aggregate_with_body(&server, r#"{"q": "*"}"#));
+        assert_eq!(status, status::BadRequest);
+        assert_eq!(body["error"], "Parameter aggs is missing from the request");
+        let (status, _) = error_response(aggregate_with_body(
+            &server,
+            r#"{"q": "*", "aggs": {"langs": {"terms": {"size": 2}}}}"#,
+        ));
+        assert_eq!(status, status::BadRequest);
+    }
+
+    #[test]
+    fn aggregation_limits_are_bad_requests() {
+        let langs = r#"{"q": "*", "aggs": {"langs": {"terms": {"field": "lang"}}}}"#;
+        let server = test_server(
+            QuerySettings::default(),
+            AggregationLimits {
+                memory_limit: None,
+                bucket_limit: Some(1),
+            },
+        );
+        let (status, _) = error_response(aggregate_with_body(&server, langs));
+        assert_eq!(status, status::BadRequest);
+
+        let server = test_server(
+            QuerySettings::default(),
+            AggregationLimits {
+                memory_limit: Some(1),
+                bucket_limit: None,
+            },
+        );
+        let (status, _) = error_response(aggregate_with_body(&server, langs));
+        assert_eq!(status, status::BadRequest);
+    }
+
+    #[test]
+    fn autocomplete_distinct_values() {
+        let mut schema_builder = Schema::builder();
+        let title = schema_builder.add_text_field("title", TEXT | STORED);
+        schema_builder.add_text_field("lang", STRING);
+        let mut titles = vec!["paris hotel", "paris city", "lyon"];
+        // Documents sharing the same value rank above the others.
+        titles.extend(["paris paris"; 30]);
+        let docs = titles
+            .into_iter()
+            .map(|title_text| doc!(title => title_text));
+        let index = create_index(schema_builder.build(), docs);
+        let server = index_server(index, QuerySettings::default(), unlimited_aggregations());
+        let suggestions = |n: &str| -> Vec<String> {
+            autocomplete_with_params(
+                &server,
+                &params(&[("field", "title"), ("q", "paris"), ("n", n)]),
+            )
+            .unwrap()
+            .suggestions
+        };
+
+        assert_eq!(suggestions("1"), vec!["paris paris"]);
+        // 20 documents are read for 2 suggestions, all of them sharing the
+        // same value.
+        assert_eq!(suggestions("2"), vec!["paris paris"]);
+        let mut all_suggestions = suggestions("5");
+        all_suggestions.sort();
+        assert_eq!(
+            all_suggestions,
+            vec!["paris city", "paris hotel", "paris paris"]
+        );
+        assert!(suggestions("0").is_empty());
+
+        let (status, _) = error_response(autocomplete_with_params(
+            &server,
+            &params(&[("field", "title"), ("q", "paris"), ("n", "1000")]),
+        ));
+        assert_eq!(status, status::BadRequest);
+        let (status, _) = error_response(autocomplete_with_params(
+            &server,
+            &params(&[("field", "lang"), ("q", "fr")]),
+        ));
+        assert_eq!(status, status::BadRequest);
+    }
+
+    #[test]
+    fn more_like_a_hit() {
+        let server = default_server();
+        let serp = search_with_params(&server, &params(&[("q", "hotel")])).unwrap();
+        let serp = serde_json::to_value(serp).unwrap();
+        let doc_address = &serp["hits"][0]["doc_address"];
+        assert_eq!(doc_address, &json!({"segment_ord": 0, "doc_id": 1}));
+        let doc = format!("{}:{}", doc_address["segment_ord"], doc_address["doc_id"]);
+        let like_params = |target: (&str, &str)| {
+            params(&[
+                target,
+                ("min_doc_freq", "1"),
+                ("min_term_freq", "1"),
+                ("like_fields", "title"),
+            ])
+        };
+
+        let serp = more_like_this_with_params(&server, &like_params(("doc", &doc))).unwrap();
+        assert_eq!(serp.q, "0:1");
+        assert_eq!(serp.num_hits, 1);
+        let serp = serde_json::to_value(serp).unwrap();
+        assert_eq!(
+            serp["hits"][0]["doc_address"],
+            json!({"segment_ord": 0, "doc_id": 0})
+        );
+        let serp = more_like_this_with_params(&server, &like_params(("text", "lyon"))).unwrap();
+        assert_eq!(serp.num_hits, 1);
+    }
+
+    #[test]
+    fn invalid_more_like_this_requests() {
+        let server = default_server();
+        let (status, body) = error_response(more_like_this_with_params(
+            &server,
+            &params(&[("doc", "1")]),
+        ));
+        assert_eq!(status, status::BadRequest);
+        assert_eq!(
+            body["error"],
+            "Invalid document `1`, expected `segment:doc`"
+        );
+        let (status, body) = error_response(more_like_this_with_params(
+            &server,
+            &params(&[("doc", "0:1"), ("text", "paris")]),
+        ));
+        assert_eq!(status, status::BadRequest);
+        assert_eq!(
+            body["error"],
+            "Exactly one of the parameters doc and text is required"
+        );
+        let (status, _) = error_response(more_like_this_with_params(&server, &params(&[])));
+        assert_eq!(status, status::BadRequest);
+        let (status, _) = error_response(more_like_this_with_params(
+            &server,
+            &params(&[("doc", "3:0")]),
+        ));
+        assert_eq!(status, status::BadRequest);
+        let (status, _) = error_response(more_like_this_with_params(
+            &server,
+            &params(&[("text", "paris"), ("like_fields", "missing")]),
+        ));
+        assert_eq!(status, status::BadRequest);
+    }
+}
@@ -0,0 +1,136 @@
+use ansi_term::Colour::Red;
+use ansi_term::Style;
+use std::collections::BTreeMap;
+use tantivy::query::Query;
+use tantivy::schema::FieldType;
+use tantivy::snippet::{collapse_overlapped_ranges, Snippet, SnippetGenerator};
+use tantivy::{Searcher, TantivyDocument, TantivyError};
+
+/// How the terms matching the query are highlighted in a snippet.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SnippetFormat {
+    /// HTML, with the matching terms in `<b>` tags.
+    Html,
+    /// Text for the terminal, with the matching terms in bold red.
+    Ansi,
+}
+
+/// Snippet generators for the fields requested by the user.
+pub struct Snippets {
+    generators: Vec<(String, SnippetGenerator)>,
+    format: SnippetFormat,
+}
+
+impl Snippets {
+    /// Creates a snippet generator for each field. The fields must be stored
+    /// and indexed text fields.
+    pub fn create(
+        searcher: &Searcher,
+        query: &dyn Query,
+        field_names: &[String],
+        format: SnippetFormat,
+    ) -> tantivy::Result<Snippets> {
+        let schema = searcher.schema();
+        let mut generators = Vec::new();
+        for field_name in field_names {
+            let field = schema.get_field(field_name)?;
+            let field_entry = schema.get_field_entry(field);
+            let is_indexed_text = match field_entry.field_type() {
+                FieldType::Str(text_options) => text_options.get_indexing_options().is_some(),
+                _ => false,
+            };
+            if !is_indexed_text || !field_entry.is_stored() {
+                return Err(TantivyError::InvalidArgument(format!(
+                    "Field `{}` must be a stored and indexed text field to build snippets",
+                    field_name
+                )));
+            }
+            let generator = SnippetGenerator::create(searcher, query, field)?;
+            generators.push((field_name.clone(), generator));
+        }
+        Ok(Snippets { generators, format })
+    }
+
+    /// Whether no snippet was requested.
+    pub fn is_empty(&self) -> bool {
+        self.generators.is_empty()
+    }
+
+    /// Returns the snippet of each field, skipping the fields without any
+    /// matching term.
+    pub fn render(&self, doc: &TantivyDocument) -> BTreeMap<String, String> {
+        self.generators
+            .iter()
+            .filter_map(|(field_name, generator)| {
+                let snippet = generator.snippet_from_doc(doc);
+                if snippet.is_empty() {
+                    return None;
+                }
+                let fragment = match self.format {
+                    SnippetFormat::Html => snippet.to_html(),
+                    SnippetFormat::Ansi => snippet_to_ansi(&snippet),
+                };
+                Some((field_name.clone(), fragment))
+            })
+            .collect()
+    }
+}
+
+/// Renders a snippet for the terminal, highlighting the matching terms.
+fn snippet_to_ansi(snippet: &Snippet) -> String {
+    let highlight = Style::new().bold().fg(Red);
+    let fragment = snippet.fragment();
+    let mut text = String::new();
+    let mut start_from = 0;
+    for range in collapse_overlapped_ranges(snippet.highlighted()) {
+        text.push_str(&fragment[start_from..range.start]);
+        text.push_str(&highlight.paint(&fragment[range.clone()]).to_string());
+        start_from = range.end;
+    }
+    text.push_str(&fragment[start_from..]);
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_index::create_index;
+    use tantivy::doc;
+    use tantivy::query::QueryParser;
+    use tantivy::schema::{Schema, STORED, TEXT};
+
+    fn render_snippets(format: SnippetFormat) -> BTreeMap<String, String> {
+        let mut schema_builder = Schema::builder();
+        let title = schema_builder.add_text_field("title", TEXT | STORED);
+        let body = schema_builder.add_text_field("body", TEXT | STORED);
+        let doc = doc!(title => "Paris", body => "Paris is the capital of France");
+        let index = create_index(schema_builder.build(), [doc.clone()]);
+        let searcher = index.reader().unwrap().searcher();
+        let query = QueryParser::for_index(&index, vec![body])
+            .parse_query("capital")
+            .unwrap();
+        let field_names = vec!["title".to_string(), "body".to_string()];
+        Snippets::create(&searcher, &query, &field_names, format)
+            .unwrap()
+            .render(&doc)
+    }
+
+    #[test]
+    fn html_snippets() {
+        let snippets = render_snippets(SnippetFormat::Html);
+        assert_eq!(snippets.len(), 1);
+        assert_eq!(snippets["body"], "Paris is the <b>capital</b> of France");
+    }
+
+    #[test]
+    fn ansi_snippets() {
+        let snippets = render_snippets(SnippetFormat::Ansi);
+        assert_eq!(
+            snippets["body"],
+            format!(
+                "Paris is the {} of France",
+                Style::new().bold().fg(Red).paint("capital")
+            )
+        );
+    }
+}
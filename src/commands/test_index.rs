@@ -0,0 +1,13 @@
+use tantivy::schema::Schema;
+use tantivy::{Index, TantivyDocument};
+
+/// Creates an index in RAM holding `docs`, committed as a single segment.
+pub fn create_index(schema: Schema, docs: impl IntoIterator<Item = TantivyDocument>) -> Index {
+    let index = Index::create_in_ram(schema);
+    let mut index_writer = index.writer_with_num_threads(1, 15_000_000).unwrap();
+    for doc in docs {
+        index_writer.add_document(doc).unwrap();
+    }
+    index_writer.commit().unwrap();
+    index
+}
@@ -0,0 +1,339 @@
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use tantivy::schema::{FieldType, Schema};
+use tantivy::tokenizer::{
+    AlphaNumOnlyFilter, AsciiFoldingFilter, Language, LowerCaser, NgramTokenizer, RawTokenizer,
+    RegexTokenizer, RemoveLongFilter, SimpleTokenizer, Stemmer, StopWordFilter, TextAnalyzer,
+    TextAnalyzerBuilder, TokenizerManager, WhitespaceTokenizer,
+};
+use tantivy::{Index, TantivyError};
+
+/// Name of the file, stored alongside `meta.json`, holding the
+/// custom tokenizer definitions of an index.
+pub const TOKENIZERS_FILENAME: &str = "tokenizers.json";
+
+/// Tokenizers registered by tantivy itself. They cannot be redefined.
+pub const BUILTIN_TOKENIZERS: [&str; 4] = ["default", "en_stem", "raw", "whitespace"];
+
+/// Tokenizer at the head of a custom pipeline.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BaseTokenizer {
+    Simple,
+    Whitespace,
+    Raw,
+    Ngram {
+        min_gram: usize,
+        max_gram: usize,
+        #[serde(default)]
+        prefix_only: bool,
+    },
+    Regex {
+        pattern: String,
+    },
+}
+
+/// Token filter applied, in order, after the base tokenizer.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TokenFilterConfig {
+    Lowercase,
+    AsciiFolding,
+    AlphaNumOnly,
+    RemoveLong {
+        limit: usize,
+    },
+    Stemmer {
+        language: Language,
+    },
+    StopWords {
+        #[serde(default)]
+        language: Option<Language>,
+        #[serde(default)]
+        words: Vec<String>,
+    },
+}
+
+/// Definition of a custom tokenizer pipeline.
+///
+/// For instance
+///
+/// ```json
+/// {
+///   "en_stem_ascii": {
+///     "tokenizer": {"type": "simple"},
+///     "filters": [
+///       {"type": "remove_long", "limit": 40},
+///       {"type": "lowercase"},
+///       {"type": "ascii_folding"},
+///       {"type": "stemmer", "language": "English"}
+///     ]
+///   }
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TokenizerConfig {
+    pub tokenizer: BaseTokenizer,
+    #[serde(default)]
+    pub filters: Vec<TokenFilterConfig>,
+}
+
+/// Custom tokenizers of an index, by name.
+pub type TokenizerConfigs = BTreeMap<String, TokenizerConfig>;
+
+impl TokenizerConfig {
+    pub fn build(&self) -> tantivy::Result<TextAnalyzer> {
+        let mut builder: TextAnalyzerBuilder = match &self.tokenizer {
+            BaseTokenizer::Simple => TextAnalyzer::builder(SimpleTokenizer::default()).dynamic(),
+            BaseTokenizer::Whitespace => {
+                TextAnalyzer::builder(WhitespaceTokenizer::default()).dynamic()
+            }
+            BaseTokenizer::Raw => TextAnalyzer::builder(RawTokenizer::default()).dynamic(),
+            BaseTokenizer::Ngram {
+                min_gram,
+                max_gram,
+                prefix_only,
+            } => TextAnalyzer::builder(NgramTokenizer::new(*min_gram, *max_gram, *prefix_only)?)
+                .dynamic(),
+            BaseTokenizer::Regex { pattern } => {
+                TextAnalyzer::builder(RegexTokenizer::new(pattern)?).dynamic()
+            }
+        };
+        for filter in &self.filters {
+            builder = match filter {
+                TokenFilterConfig::Lowercase => builder.filter_dynamic(LowerCaser),
+                TokenFilterConfig::AsciiFolding => builder.filter_dynamic(AsciiFoldingFilter),
+                TokenFilterConfig::AlphaNumOnly => builder.filter_dynamic(AlphaNumOnlyFilter),
+                TokenFilterConfig::RemoveLong { limit } => {
+                    builder.filter_dynamic(RemoveLongFilter::limit(*limit))
+                }
+                TokenFilterConfig::Stemmer { language } => {
+                    builder.filter_dynamic(Stemmer::new(*language))
+                }
+                TokenFilterConfig::StopWords { language, words } => {
+                    if let Some(language) = language {
+                        let stop_word_filter = StopWordFilter::new(*language).ok_or_else(|| {
+                            TantivyError::InvalidArgument(format!(
+                                "No stop words available for {:?}",
+                                language
+                            ))
+                        })?;
+                        builder = builder.filter_dynamic(stop_word_filter);
+                    }
+                    builder.filter_dynamic(StopWordFilter::remove(words.clone()))
+                }
+            };
+        }
+        Ok(builder.build())
+    }
+}
+
+/// Reads the custom tokenizers stored in an index directory.
+///
+/// An index without a tokenizers file simply has no custom tokenizers.
+pub fn load_tokenizer_configs(directory: &Path) -> tantivy::Result<TokenizerConfigs> {
+    match fs::read_to_string(directory.join(TOKENIZERS_FILENAME)) {
+        Ok(json) => parse_tokenizer_configs(&json),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(TokenizerConfigs::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Parses and validates a tokenizers definition.
+pub fn parse_tokenizer_configs(json: &str) -> tantivy::Result<TokenizerConfigs> {
+    let configs: TokenizerConfigs = serde_json::from_str(json).map_err(|e| {
+        TantivyError::InvalidArgument(format!("Invalid tokenizers definition: {}", e))
+    })?;
+    for (name, config) in &configs {
+        if BUILTIN_TOKENIZERS.contains(&name.as_str()) {
+            return Err(TantivyError::InvalidArgument(format!(
+                "Tokenizer `{}` is built-in and cannot be redefined",
+                name
+            )));
+        }
+        config.build().map_err(|e| {
+            TantivyError::InvalidArgument(format!("Invalid tokenizer `{}`: {}", name, e))
+        })?;
+    }
+    Ok(configs)
+}
+
+/// Writes the custom tokenizers next to the index `meta.json`.
+pub fn save_tokenizer_configs(directory: &Path, configs: &TokenizerConfigs) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(configs).map_err(io::Error::other)?;
+    fs::write(directory.join(TOKENIZERS_FILENAME), json)
+}
+
+/// Registers the custom tokenizers both for indexing and for fast fields.
+pub fn register_tokenizers(index: &Index, configs: &TokenizerConfigs) -> tantivy::Result<()> {
+    for (name, config) in configs {
+        let text_analyzer = config.build()?;
+        index.tokenizers().register(name, text_analyzer.clone());
+        index.fast_field_tokenizer().register(name, text_analyzer);
+    }
+    Ok(())
+}
+
+/// Returns a `TokenizerManager` with tantivy's built-in tokenizers
+/// and the given custom tokenizers, as an index would have them.
+pub fn tokenizer_manager(configs: &TokenizerConfigs) -> tantivy::Result<TokenizerManager> {
+    let tokenizer_manager = TokenizerManager::default();
+    for (name, config) in configs {
+        tokenizer_manager.register(name, config.build()?);
+    }
+    Ok(tokenizer_manager)
+}
+
+/// Names of the built-in and custom tokenizers.
+pub fn tokenizer_names(configs: &TokenizerConfigs) -> Vec<String> {
+    BUILTIN_TOKENIZERS
+        .iter()
+        .map(|name| name.to_string())
+        .chain(configs.keys().cloned())
+        .collect()
+}
+
+/// Checks that every tokenizer referred to by the schema is registered.
+pub fn validate_schema_tokenizers(
+    schema: &Schema,
+    tokenizer_manager: &TokenizerManager,
+) -> tantivy::Result<()> {
+    for (_, field_entry) in schema.fields() {
+        let (indexing_options, fast_tokenizer) = match field_entry.field_type() {
+            FieldType::Str(text_options) => (
+                text_options.get_indexing_options(),
+                text_options.get_fast_field_tokenizer_name(),
+            ),
+            FieldType::JsonObject(json_options) => (
+                json_options.get_text_indexing_options(),
+                json_options.get_fast_field_tokenizer_name(),
+            ),
+            _ => continue,
+        };
+        let tokenizer_names = indexing_options
+            .map(|indexing_options| indexing_options.tokenizer())
+            .into_iter()
+            .chain(fast_tokenizer);
+        for tokenizer_name in tokenizer_names {
+            if tokenizer_manager.get(tokenizer_name).is_none() {
+                return Err(TantivyError::InvalidArgument(format!(
+                    "Field `{}` uses unknown tokenizer `{}`",
+                    field_entry.name(),
+                    tokenizer_name
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Opens the index in `directory` with its custom tokenizers registered.
+///
+/// All commands should open indexes through this function.
+pub fn open_index(directory: &Path) -> tantivy::Result<Index> {
+    let index = Index::open_in_dir(directory)?;
+    let configs = load_tokenizer_configs(directory)?;
+    register_tokenizers(&index, &configs)?;
+    Ok(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tantivy::schema::{TextFieldIndexing, TextOptions};
+    use tantivy::tokenizer::TokenStream;
+
+    fn tokens(config: &TokenizerConfig, text: &str) -> Vec<String> {
+        let mut text_analyzer = config.build().unwrap();
+        let mut token_stream = text_analyzer.token_stream(text);
+        let mut tokens = Vec::new();
+        while token_stream.advance() {
+            tokens.push(token_stream.token().text.clone());
+        }
+        tokens
+    }
+
+    #[test]
+    fn parse_and_build_pipeline() {
+        let configs = parse_tokenizer_configs(
+            r#"{"en_stem_ascii": {
+                "tokenizer": {"type": "simple"},
+                "filters": [
+                    {"type": "lowercase"},
+                    {"type": "ascii_folding"},
+                    {"type": "stop_words", "language": "English"},
+                    {"type": "stemmer", "language": "English"}
+                ]
+            }}"#,
+        )
+        .unwrap();
+        let config = &configs["en_stem_ascii"];
+        assert_eq!(
+            tokens(config, "The Running Café"),
+            vec!["run".to_string(), "cafe".to_string()]
+        );
+    }
+
+    #[test]
+    fn ngram_tokenizer() {
+        let config = TokenizerConfig {
+            tokenizer: BaseTokenizer::Ngram {
+                min_gram: 2,
+                max_gram: 3,
+                prefix_only: true,
+            },
+            filters: vec![TokenFilterConfig::Lowercase],
+        };
+        assert_eq!(
+            tokens(&config, "Fox"),
+            vec!["fo".to_string(), "fox".to_string()]
+        );
+    }
+
+    #[test]
+    fn builtin_tokenizers_cannot_be_redefined() {
+        let err = parse_tokenizer_configs(r#"{"default": {"tokenizer": {"type": "raw"}}}"#);
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn schema_with_unknown_tokenizer_is_rejected() {
+        let configs =
+            parse_tokenizer_configs(r#"{"en_stem_ascii": {"tokenizer": {"type": "simple"}}}"#)
+                .unwrap();
+        let tokenizer_manager = tokenizer_manager(&configs).unwrap();
+        let schema_with_tokenizer = |tokenizer: &str| {
+            let mut schema_builder = Schema::builder();
+            schema_builder.add_text_field(
+                "title",
+                TextOptions::default()
+                    .set_indexing_options(TextFieldIndexing::default().set_tokenizer(tokenizer)),
+            );
+            schema_builder.build()
+        };
+        assert!(validate_schema_tokenizers(
+            &schema_with_tokenizer("en_stem_ascii"),
+            &tokenizer_manager
+        )
+        .is_ok());
+        assert!(
+            validate_schema_tokenizers(&schema_with_tokenizer("en_stem"), &tokenizer_manager)
+                .is_ok()
+        );
+        assert!(
+            validate_schema_tokenizers(&schema_with_tokenizer("fr_stem"), &tokenizer_manager)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn invalid_ngram_is_rejected() {
+        let err = parse_tokenizer_configs(
+            r#"{"grams": {"tokenizer": {"type": "ngram", "min_gram": 3, "max_gram": 1}}}"#,
+        );
+        assert!(err.is_err());
+    }
+}
@@ -1,6 +1,6 @@
 use std::io::Write;
 
-use clap::{Arg, Command};
+use clap::{Arg, ArgAction, ArgGroup, Command};
 mod commands;
 pub mod timer;
 use self::commands::*;
@@ -14,6 +14,38 @@ fn main() {
         .value_name("directory")
         .help("Tantivy index directory filepath")
         .required(true);
+    let multi_index_arg = index_arg
+        .clone()
+        .help("Tantivy index directory filepath. Can be repeated to search several indexes with the same schema")
+        .action(ArgAction::Append);
+
+    let default_fields_arg = Arg::new("default_fields")
+        .long("default-fields")
+        .value_name("fields")
+        .help("Comma separated list of the fields searched when a query does not target a specific field");
+
+    let boost_arg = Arg::new("boost")
+        .long("boost")
+        .value_name("field=boost")
+        .help("Boost the terms of a field, for instance title=2.0. Can be repeated")
+        .action(ArgAction::Append);
+
+    let conjunction_arg = Arg::new("conjunction")
+        .long("conjunction")
+        .help("Require all the terms of a query to match, instead of any of them")
+        .action(ArgAction::SetTrue);
+
+    let no_conjunction_arg = Arg::new("no_conjunction")
+        .long("no-conjunction")
+        .help("Match the documents containing any of the terms of a query, even if the index settings require all of them")
+        .conflicts_with("conjunction")
+        .action(ArgAction::SetTrue);
+
+    let fuzzy_arg = Arg::new("fuzzy")
+        .long("fuzzy")
+        .value_name("field[:distance][:prefix][:no-transposition]")
+        .help("Also match the terms of a text field within an edit distance, 1 by default. Can be repeated")
+        .action(ArgAction::Append);
 
     let cli_options = Command::new("Tantivy")
         .subcommand_required(true)
@@ -35,11 +67,15 @@ fn main() {
                     .help("How many JSON documents to sample while inferring schema (0 means no limit)")
                     .default_value("10000")
                     .value_parser(clap::value_parser!(usize)))
+                .arg(Arg::new("tokenizers")
+                    .long("tokenizers")
+                    .value_name("file")
+                    .help("JSON file defining custom tokenizers, stored alongside the index"))
         )
         .subcommand(
             Command::new("serve")
                 .about("Start a server")
-                .arg(index_arg.clone())
+                .arg(multi_index_arg.clone())
                 .arg(Arg::new("host")
                     .long("host")
                     .value_name("host")
@@ -55,6 +91,21 @@ fn main() {
                     .default_value("3000")
                     .value_parser(clap::value_parser!(usize))
                 )
+                .arg(default_fields_arg.clone())
+                .arg(boost_arg.clone())
+                .arg(conjunction_arg.clone())
+                .arg(no_conjunction_arg.clone())
+                .arg(fuzzy_arg.clone())
+                .arg(Arg::new("agg_memory_limit")
+                    .long("agg-memory-limit")
+                    .value_name("bytes")
+                    .help("Memory the aggregations of a request may use, in bytes")
+                    .value_parser(clap::value_parser!(u64)))
+                .arg(Arg::new("agg_bucket_limit")
+                    .long("agg-bucket-limit")
+                    .value_name("buckets")
+                    .help("Number of buckets the aggregations of a request may create")
+                    .value_parser(clap::value_parser!(u32)))
         )
         .subcommand(
             Command::new("index")
@@ -90,25 +141,258 @@ fn main() {
         .subcommand(
             Command::new("search")
                 .about("Search an index.")
-                .arg(index_arg.clone())
+                .arg(multi_index_arg.clone())
                 .arg(Arg::new("query")
                     .short('q')
                     .long("query")
                     .value_name("query")
                     .help("Query")
-                    .required(true))
+                    .required_unless_present_any(["queries_file", "like_doc", "like_text"]))
+                .arg(Arg::new("queries_file")
+                    .long("queries-file")
+                    .value_name("file")
+                    .help("Run each query of this file, one per line, given as text or as a JSON object with the query and its limit, offset, sort_by and lenient options. The results of each query are written as a JSON line")
+                    .conflicts_with_all(["query", "all", "count", "aggregation", "agg_file", "snippet", "explain", "facet", "output"]))
+                .arg(Arg::new("num_threads")
+                    .short('t')
+                    .long("num_threads")
+                    .value_name("num_threads")
+                    .help("Number of threads running the queries of --queries-file. By default one per core")
+                    .requires("queries_file")
+                    .value_parser(clap::value_parser!(usize)))
+                .arg(Arg::new("fields")
+                    .long("fields")
+                    .value_name("fields")
+                    .help("Comma separated list of the fields to return. Fast fields which are not stored are read from their fast field. By default all stored fields are returned."))
+                .arg(Arg::new("lenient")
+                    .long("lenient")
+                    .help("Run the valid part of an invalid query, reporting the errors on stderr, instead of failing")
+                    .action(ArgAction::SetTrue))
                 .arg(Arg::new("aggregation")
                     .short('a')
                     .long("agg")
                     .value_name("agg")
                     .help("Aggregation request as JSON")
                     .required(false))
+                .arg(Arg::new("agg_file")
+                    .long("agg-file")
+                    .value_name("file")
+                    .help("File holding the aggregation request as JSON")
+                    .conflicts_with("aggregation"))
+                .arg(default_fields_arg.clone())
+                .arg(boost_arg.clone())
+                .arg(conjunction_arg.clone())
+                .arg(no_conjunction_arg.clone())
+                .arg(fuzzy_arg.clone())
+                .arg(Arg::new("limit")
+                    .short('n')
+                    .long("limit")
+                    .value_name("limit")
+                    .help("Number of best ranked documents to return")
+                    .default_value("10")
+                    .value_parser(clap::value_parser!(usize)))
+                .arg(Arg::new("offset")
+                    .long("offset")
+                    .value_name("offset")
+                    .help("Number of best ranked documents to skip")
+                    .default_value("0")
+                    .value_parser(clap::value_parser!(usize)))
+                .arg(Arg::new("sort_by")
+                    .long("sort-by")
+                    .value_name("field[:asc|desc]")
+                    .help("Rank the documents by a u64, i64, f64 or date fast field instead of their score (descending by default)"))
+                .arg(Arg::new("snippet")
+                    .long("snippet")
+                    .value_name("field")
+                    .help("Add a snippet of this stored text field, highlighting the terms matching the query, to each hit. Can be repeated")
+                    .action(ArgAction::Append))
+                .arg(Arg::new("snippet_format")
+                    .long("snippet-format")
+                    .value_name("format")
+                    .help("Highlight the snippets with html tags, or with terminal colors")
+                    .value_parser(["html", "ansi"])
+                    .default_value("html"))
+                .arg(Arg::new("explain")
+                    .long("explain")
+                    .value_name("format")
+                    .help("Explain the score of each hit, as JSON in the hit or as a text tree after it")
+                    .num_args(0..=1)
+                    .value_parser(["json", "tree"])
+                    .default_missing_value("json"))
+                .arg(Arg::new("output")
+                    .long("output")
+                    .value_name("format")
+                    .help("Output format of the hits, or of the aggregation results with json and table. csv and table only show the fields of the documents")
+                    .default_value("ndjson")
+                    .value_parser(["ndjson", "json", "csv", "table"]))
+                .arg(Arg::new("facet")
+                    .long("facet")
+                    .value_name("[field:]/path")
+                    .help("Count the matching documents under each child of this facet path, writing the counts to stderr. The field can be left out if the index has a single facet field. Can be repeated")
+                    .action(ArgAction::Append))
+                .arg(Arg::new("all")
+                    .long("all")
+                    .help("Stream all the matching documents, unranked, instead of the best ranked ones")
+                    .action(ArgAction::SetTrue)
+                    .conflicts_with_all(["limit", "offset", "sort_by", "snippet", "explain", "facet"]))
+                .arg(Arg::new("count")
+                    .long("count")
+                    .help("Only write the number of matching documents, without reading them")
+                    .action(ArgAction::SetTrue)
+                    .conflicts_with_all(["all", "limit", "offset", "sort_by", "snippet", "explain", "fields", "output", "aggregation", "agg_file", "facet"]))
+                .arg(Arg::new("like_doc")
+                    .long("like-doc")
+                    .value_name("segment:doc")
+                    .help("Find the documents most like this document, given by the segment_ord and doc_id of its doc_address. With several indexes, the document is read from the first one"))
+                .arg(Arg::new("like_text")
+                    .long("like-text")
+                    .value_name("text")
+                    .help("Find the documents most like this text"))
+                .group(ArgGroup::new("like")
+                    .args(["like_doc", "like_text"])
+                    .conflicts_with_all(["query", "queries_file", "count", "aggregation", "agg_file", "sort_by", "lenient"]))
+                .arg(Arg::new("like_fields")
+                    .long("like-fields")
+                    .value_name("fields")
+                    .help("Comma separated list of the text fields the terms of --like-doc or --like-text are drawn from and searched in. By default all indexed text fields")
+                    .requires("like"))
+                .arg(Arg::new("min_doc_freq")
+                    .long("min-doc-freq")
+                    .value_name("count")
+                    .help("Ignore the terms found in fewer documents (5 by default)")
+                    .requires("like")
+                    .value_parser(clap::value_parser!(u64)))
+                .arg(Arg::new("min_term_freq")
+                    .long("min-term-freq")
+                    .value_name("count")
+                    .help("Ignore the terms occurring fewer times in the liked document or text (2 by default)")
+                    .requires("like")
+                    .value_parser(clap::value_parser!(usize)))
+                .arg(Arg::new("max_query_terms")
+                    .long("max-query-terms")
+                    .value_name("count")
+                    .help("Maximum number of terms, the most distinctive ones, searched for (25 by default)")
+                    .requires("like")
+                    .value_parser(clap::value_parser!(usize)))
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Export the documents of an index, so that they can be indexed again.")
+                .arg(index_arg.clone())
+                .arg(Arg::new("query")
+                    .short('q')
+                    .long("query")
+                    .value_name("query")
+                    .help("Only export the documents matching this query"))
+                .arg(Arg::new("fields")
+                    .long("fields")
+                    .value_name("fields")
+                    .help("Comma separated list of the stored fields to export. By default all stored fields are exported."))
+                .arg(Arg::new("format")
+                    .long("format")
+                    .value_name("format")
+                    .help("Output format")
+                    .default_value("ndjson")
+                    .value_parser(["ndjson", "csv"]))
         )
         .subcommand(
             Command::new("inspect")
                 .about("Inspect an index.")
                 .arg(index_arg.clone())
         )
+        .subcommand(
+            Command::new("reindex")
+                .about("Copy the documents of an index into another index with a different schema")
+                .arg(Arg::new("from")
+                    .long("from")
+                    .value_name("directory")
+                    .help("Source index directory. Only its stored fields can be copied.")
+                    .required(true))
+                .arg(Arg::new("to")
+                    .long("to")
+                    .value_name("directory")
+                    .help("Target index directory, created beforehand with `new`")
+                    .required(true))
+                .arg(Arg::new("mapping")
+                    .long("mapping")
+                    .value_name("file")
+                    .help("JSON object mapping source field names to target field names"))
+                .arg(Arg::new("query")
+                    .short('q')
+                    .long("query")
+                    .value_name("query")
+                    .help("Only copy the documents matching this query"))
+                .arg(Arg::new("num_threads")
+                    .short('t')
+                    .long("num_threads")
+                    .value_name("num_threads")
+                    .help("Number of indexing threads")
+                    .default_value("1")
+                    .value_parser(clap::value_parser!(usize)))
+                .arg(Arg::new("memory_size")
+                    .short('m')
+                    .long("memory_size")
+                    .value_name("memory_size")
+                    .help("Total memory_size in bytes. It will be split for the different threads.")
+                    .default_value("1000000000")
+                    .value_parser(clap::value_parser!(usize)))
+        )
+        .subcommand(
+            Command::new("schema")
+                .about("Inspect or modify the schema of an index.")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("add-field")
+                        .about("Add fields to an existing index. All its fields must be stored, as documents are copied from the doc store.")
+                        .arg(index_arg.clone())
+                )
+                .subcommand(
+                    Command::new("show")
+                        .about("Show the fields of an index and their options.")
+                        .arg(index_arg.clone())
+                        .arg(Arg::new("json")
+                            .long("json")
+                            .help("Print the schema as JSON, as stored in meta.json")
+                            .action(ArgAction::SetTrue))
+                )
+                .subcommand(
+                    Command::new("query-settings")
+                        .about("Show or change the default fields and boosts used to parse queries on an index.")
+                        .arg(index_arg.clone())
+                        .arg(default_fields_arg.clone())
+                        .arg(boost_arg.clone())
+                        .arg(conjunction_arg.clone())
+                        .arg(no_conjunction_arg.clone())
+                        .arg(fuzzy_arg.clone())
+                )
+                .subcommand(
+                    Command::new("diff")
+                        .about("Compare the schemas of two indexes or schema JSON files. Fails if fields were removed or changed.")
+                        .arg(Arg::new("left")
+                            .value_name("left")
+                            .help("Index directory, schema JSON file or meta.json")
+                            .required(true))
+                        .arg(Arg::new("right")
+                            .value_name("right")
+                            .help("Index directory, schema JSON file or meta.json")
+                            .required(true))
+                )
+        )
+        .subcommand(
+            Command::new("analyze")
+                .about("Show the tokens produced by a field's tokenizer for a given text.")
+                .arg(index_arg.clone())
+                .arg(Arg::new("field")
+                    .short('f')
+                    .long("field")
+                    .value_name("field")
+                    .help("Field whose tokenizer should be used")
+                    .required(true))
+                .arg(Arg::new("text")
+                    .value_name("text")
+                    .help("Text to analyze")
+                    .required(true))
+        )
         .subcommand(
             Command::new("bench")
                 .about("Run a benchmark on your index")
@@ -126,6 +410,11 @@ fn main() {
                     .help("Number of times to repeat the benchmark.")
                     .default_value("1")
                     .value_parser(clap::value_parser!(usize)))
+                .arg(default_fields_arg.clone())
+                .arg(boost_arg.clone())
+                .arg(conjunction_arg.clone())
+                .arg(no_conjunction_arg.clone())
+                .arg(fuzzy_arg.clone())
         )
         .subcommand(
             Command::new("merge")
@@ -141,6 +430,10 @@ fn main() {
         "serve" => run_serve_cli,
         "search" => run_search_cli,
         "inspect" => run_inspect_cli,
+        "analyze" => run_analyze_cli,
+        "schema" => run_schema_cli,
+        "reindex" => run_reindex_cli,
+        "export" => run_export_cli,
         "merge" => run_merge_cli,
         "bench" => run_bench_cli,
         _ => panic!("Subcommand {} is unknown", subcommand),